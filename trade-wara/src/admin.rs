@@ -0,0 +1,123 @@
+use serde::Deserialize;
+
+use crate::{kill_switch::KillScope, order_book::Halt, registry::Instrument};
+
+/// Commands of the market operator, received apart from the orders.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "command")]
+pub enum AdminCommand {
+    /// Lists a new instrument or replaces the definition of a listed one.
+    UpsertInstrument(Instrument),
+    /// Stops trading an asset, see `order_book::Halt`.
+    Halt {
+        asset_id: String,
+        #[serde(flatten)]
+        halt: Halt,
+    },
+    Resume {
+        asset_id: String,
+    },
+    /// Blocks an investor or the whole market, see `KillSwitch`.
+    Kill {
+        #[serde(flatten)]
+        scope: KillScope,
+        reason: Option<String>,
+        operator: Option<String>,
+    },
+    Enable {
+        #[serde(flatten)]
+        scope: KillScope,
+        operator: Option<String>,
+    },
+    /// Shares located for an investor to borrow and sell short.
+    Locate {
+        investor_id: String,
+        asset_id: String,
+        shares: u32,
+    },
+    /// Deposits cash into the margin account of an investor, opening it on
+    /// the first deposit.
+    MarginDeposit {
+        investor_id: String,
+        cash: f32,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserialize_upsert_instrument() {
+        let json = r#"{
+            "command": "UpsertInstrument",
+            "id": "HGLG11",
+            "name": "CSHG Logística FII",
+            "currency": "BRL",
+            "status": "Inactive"
+        }"#;
+
+        let Ok(AdminCommand::UpsertInstrument(instrument)) =
+            serde_json::from_str(json)
+        else {
+            panic!("Expected an instrument update");
+        };
+
+        assert_eq!("HGLG11", instrument.asset().id());
+        assert_eq!(
+            crate::registry::InstrumentStatus::Inactive,
+            instrument.status()
+        );
+    }
+
+    #[test]
+    fn deserialize_halt() {
+        let json = r#"{
+            "command": "Halt",
+            "asset_id": "HGLG11",
+            "reason": "Corporate event",
+            "cancel_all": true
+        }"#;
+
+        let command: AdminCommand = serde_json::from_str(json).unwrap();
+
+        assert_eq!(
+            AdminCommand::Halt {
+                asset_id: "HGLG11".into(),
+                halt: Halt::new()
+                    .with_reason("Corporate event")
+                    .with_cancel_all(),
+            },
+            command
+        );
+    }
+
+    #[test]
+    fn deserialize_kill() {
+        let json = r#"{
+            "command": "Kill",
+            "scope": "Investor",
+            "investor_id": "123",
+            "operator": "compliance"
+        }"#;
+
+        assert_eq!(
+            AdminCommand::Kill {
+                scope: KillScope::Investor {
+                    investor_id: "123".into()
+                },
+                reason: None,
+                operator: Some("compliance".into()),
+            },
+            serde_json::from_str(json).unwrap()
+        );
+        assert_eq!(
+            AdminCommand::Enable {
+                scope: KillScope::Market,
+                operator: None,
+            },
+            serde_json::from_str(r#"{"command": "Enable", "scope": "Market"}"#)
+                .unwrap()
+        );
+    }
+}
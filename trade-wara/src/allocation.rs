@@ -0,0 +1,134 @@
+use serde::Deserialize;
+
+/// How the shares traded by an incoming order are shared among the orders
+/// resting at the best price of the other side.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum MatchingPolicy {
+    /// Price-time priority, the oldest order is filled first.
+    #[default]
+    Fifo,
+    /// In proportion to the pending shares of each order.
+    ProRata,
+    /// The oldest order is filled first, the rest pro-rata.
+    ProRataTopOrder,
+}
+
+/// Shares allocated to each order of a price level, given their pending
+/// shares in time priority. At most the shares of the level are allocated.
+///
+/// Pro-rata allocations are rounded down and the shares left by the rounding
+/// are given one at a time in time priority, so they always sum to the
+/// traded shares.
+pub fn allocate(
+    policy: MatchingPolicy,
+    shares: u32,
+    level: &[u32],
+) -> Vec<u32> {
+    let shares = shares.min(level.iter().sum());
+
+    match policy {
+        MatchingPolicy::Fifo => fifo(shares, level),
+        MatchingPolicy::ProRata => pro_rata(shares, level),
+        MatchingPolicy::ProRataTopOrder => {
+            let Some((top, rest)) = level.split_first() else {
+                return vec![];
+            };
+
+            let top_shares = shares.min(*top);
+
+            let mut allocations = vec![top_shares];
+            allocations.extend(pro_rata(shares - top_shares, rest));
+            allocations
+        }
+    }
+}
+
+fn fifo(mut shares: u32, level: &[u32]) -> Vec<u32> {
+    level
+        .iter()
+        .map(|pending_shares| {
+            let allocated = shares.min(*pending_shares);
+            shares -= allocated;
+            allocated
+        })
+        .collect()
+}
+
+fn pro_rata(shares: u32, level: &[u32]) -> Vec<u32> {
+    let total: u64 = level.iter().map(|shares| *shares as u64).sum();
+
+    if total == 0 {
+        return vec![0; level.len()];
+    }
+
+    let mut allocations = level
+        .iter()
+        .map(|pending_shares| {
+            (shares as u64 * *pending_shares as u64 / total) as u32
+        })
+        .collect::<Vec<_>>();
+
+    // Each order is short of less than a share, so one pass is enough
+    let mut remainder = shares - allocations.iter().sum::<u32>();
+
+    for (allocated, pending_shares) in allocations.iter_mut().zip(level) {
+        if remainder == 0 {
+            break;
+        }
+
+        if *allocated < *pending_shares {
+            *allocated += 1;
+            remainder -= 1;
+        }
+    }
+
+    allocations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocate_pro_rata() {
+        assert_eq!(
+            vec![4, 10, 19],
+            allocate(MatchingPolicy::ProRata, 33, &[10, 30, 60])
+        );
+        assert_eq!(
+            vec![10, 30, 60],
+            allocate(MatchingPolicy::ProRata, 500, &[10, 30, 60])
+        );
+        assert_eq!(
+            vec![1, 1, 0],
+            allocate(MatchingPolicy::ProRata, 2, &[1, 1, 1])
+        );
+        assert_eq!(
+            vec![10, 8, 15],
+            allocate(MatchingPolicy::ProRataTopOrder, 33, &[10, 30, 60])
+        );
+        assert_eq!(
+            vec![10, 23, 0],
+            allocate(MatchingPolicy::Fifo, 33, &[10, 30, 60])
+        );
+
+        // "Allocations should always sum to the traded shares"
+        let level = [7, 1, 13, 100, 3, 29];
+
+        for policy in [
+            MatchingPolicy::Fifo,
+            MatchingPolicy::ProRata,
+            MatchingPolicy::ProRataTopOrder,
+        ] {
+            for shares in 0..=level.iter().sum() {
+                let allocations = allocate(policy, shares, &level);
+
+                assert_eq!(shares, allocations.iter().sum::<u32>());
+                assert!(allocations
+                    .iter()
+                    .zip(level)
+                    .all(|(allocated, pending)| *allocated <= pending));
+            }
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use serde::Serialize;
+
+/// Single price at which a call auction uncrosses, with the executable
+/// volume and the signed surplus left at that price (positive when buying).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Equilibrium {
+    pub price: f32,
+    pub volume: u32,
+    pub imbalance: i64,
+}
+
+/// Finds the uncrossing price for the given price levels. Among every limit
+/// price the one that maximizes the executed volume is chosen, ties are broken
+/// by the minimum imbalance, then by the closest price to `reference_price`
+/// and finally by the lowest price.
+pub fn equilibrium(
+    bids: &[(f32, u32)],
+    asks: &[(f32, u32)],
+    reference_price: Option<f32>,
+) -> Option<Equilibrium> {
+    let mut best: Option<Equilibrium> = None;
+
+    let prices = bids.iter().chain(asks.iter()).map(|(price, _)| *price);
+
+    for price in prices {
+        let demand: u64 = bids
+            .iter()
+            .filter(|(bid, _)| *bid >= price)
+            .map(|(_, shares)| *shares as u64)
+            .sum();
+
+        let supply: u64 = asks
+            .iter()
+            .filter(|(ask, _)| *ask <= price)
+            .map(|(_, shares)| *shares as u64)
+            .sum();
+
+        let candidate = Equilibrium {
+            price,
+            volume: demand.min(supply) as u32,
+            imbalance: demand as i64 - supply as i64,
+        };
+
+        if candidate.volume == 0 {
+            continue;
+        }
+
+        let is_best = match best {
+            Some(current) => is_better(&candidate, &current, reference_price),
+            None => true,
+        };
+
+        if is_best {
+            best = Some(candidate);
+        }
+    }
+
+    best
+}
+
+fn is_better(
+    candidate: &Equilibrium,
+    current: &Equilibrium,
+    reference_price: Option<f32>,
+) -> bool {
+    if candidate.volume != current.volume {
+        return candidate.volume > current.volume;
+    }
+
+    if candidate.imbalance.abs() != current.imbalance.abs() {
+        return candidate.imbalance.abs() < current.imbalance.abs();
+    }
+
+    if let Some(reference) = reference_price {
+        let candidate_distance = (candidate.price - reference).abs();
+        let current_distance = (current.price - reference).abs();
+
+        if candidate_distance != current_distance {
+            return candidate_distance < current_distance;
+        }
+    }
+
+    candidate.price < current.price
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maximize_volume() {
+        let bids = [(10.2, 300), (10.1, 200), (10.0, 500)];
+        let asks = [(9.9, 100), (10.0, 200), (10.1, 400)];
+
+        let equilibrium = equilibrium(&bids, &asks, None).unwrap();
+
+        // At 10.1 demand is 500 and supply is 700
+        assert_eq!(10.1, equilibrium.price);
+        assert_eq!(500, equilibrium.volume);
+        assert_eq!(-200, equilibrium.imbalance);
+    }
+
+    #[test]
+    fn minimize_imbalance() {
+        let bids = [(10.1, 100), (9.9, 50)];
+        let asks = [(9.9, 100)];
+
+        // 9.9 and 10.1 both execute 100 shares, only the latter has no surplus
+        let equilibrium = equilibrium(&bids, &asks, None).unwrap();
+
+        assert_eq!(10.1, equilibrium.price);
+        assert_eq!(100, equilibrium.volume);
+        assert_eq!(0, equilibrium.imbalance);
+    }
+
+    #[test]
+    fn break_ties_by_reference_price() {
+        let bids = [(10.5, 100)];
+        let asks = [(10.0, 100)];
+
+        assert_eq!(10.0, equilibrium(&bids, &asks, None).unwrap().price);
+        assert_eq!(10.5, equilibrium(&bids, &asks, Some(10.4)).unwrap().price);
+    }
+
+    #[test]
+    fn no_crossing_orders() {
+        assert_eq!(None, equilibrium(&[(9.0, 100)], &[(10.0, 100)], None));
+        assert_eq!(None, equilibrium(&[], &[(10.0, 100)], Some(10.0)));
+    }
+}
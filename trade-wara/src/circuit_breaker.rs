@@ -0,0 +1,117 @@
+use chrono::Duration;
+
+/// Price limits of an asset, both bands are percentages around a price.
+///
+/// The static band is set around the reference price and rejects orders
+/// priced outside of it. The dynamic band is set around the last traded
+/// price, a trade breaching it stops continuous trading and starts a
+/// volatility auction instead.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PriceBands {
+    static_band: Option<f32>,
+    dynamic_band: Option<f32>,
+    volatility_auction: Duration,
+}
+
+impl Default for PriceBands {
+    fn default() -> Self {
+        PriceBands {
+            static_band: None,
+            dynamic_band: None,
+            volatility_auction: Duration::minutes(5),
+        }
+    }
+}
+
+impl PriceBands {
+    pub fn new() -> PriceBands {
+        PriceBands::default()
+    }
+
+    pub fn with_static_band(mut self, percentage: f32) -> PriceBands {
+        self.static_band = Some(percentage);
+        self
+    }
+
+    pub fn with_dynamic_band(mut self, percentage: f32) -> PriceBands {
+        self.dynamic_band = Some(percentage);
+        self
+    }
+
+    pub fn with_volatility_auction(mut self, duration: Duration) -> PriceBands {
+        self.volatility_auction = duration;
+        self
+    }
+
+    /// Lowest and highest prices accepted around `reference_price`.
+    pub fn static_limits(&self, reference_price: f32) -> Option<(f32, f32)> {
+        self.static_band
+            .map(|percentage| limits(reference_price, percentage))
+    }
+
+    pub fn breaches_static(
+        &self,
+        price: f32,
+        reference_price: Option<f32>,
+    ) -> bool {
+        breaches(price, reference_price, self.static_band)
+    }
+
+    pub fn breaches_dynamic(
+        &self,
+        price: f32,
+        last_price: Option<f32>,
+    ) -> bool {
+        breaches(price, last_price, self.dynamic_band)
+    }
+
+    pub fn volatility_auction(&self) -> Duration {
+        self.volatility_auction
+    }
+}
+
+fn limits(price: f32, percentage: f32) -> (f32, f32) {
+    let range = price * percentage / 100.0;
+
+    (price - range, price + range)
+}
+
+fn breaches(price: f32, reference: Option<f32>, band: Option<f32>) -> bool {
+    let (Some(reference), Some(band)) = (reference, band) else {
+        return false;
+    };
+
+    let (low, high) = limits(reference, band);
+
+    price < low || price > high
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_static_band() {
+        let bands = PriceBands::new().with_static_band(10.0);
+
+        assert_eq!(Some((9.0, 11.0)), bands.static_limits(10.0));
+
+        assert!(!bands.breaches_static(9.0, Some(10.0)));
+        assert!(!bands.breaches_static(11.0, Some(10.0)));
+        assert!(bands.breaches_static(11.5, Some(10.0)));
+        assert!(bands.breaches_static(8.5, Some(10.0)));
+
+        // "Without a reference price every order should be accepted"
+        assert!(!bands.breaches_static(1000.0, None));
+    }
+
+    #[test]
+    fn check_dynamic_band() {
+        let bands = PriceBands::new().with_dynamic_band(2.0);
+
+        assert!(!bands.breaches_dynamic(10.1, Some(10.0)));
+        assert!(bands.breaches_dynamic(10.3, Some(10.0)));
+        assert!(!bands.breaches_static(10.3, Some(10.0)));
+        assert!(!PriceBands::new().breaches_dynamic(10.3, Some(10.0)));
+    }
+}
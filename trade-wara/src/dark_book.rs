@@ -0,0 +1,195 @@
+use std::cmp;
+
+use chrono::{DateTime, Utc};
+
+use crate::entities::{
+    order::{Buy, Open, Order, OrderInstructions, OrderTransition, Sell, Side},
+    transaction::Transaction,
+};
+
+/// Non-displayed orders of an asset. They only cross with each other at the
+/// midpoint of the lit book, in arrival order, and never show in its depth
+/// or order feed. Limit prices only bound the midpoints they accept.
+#[derive(Debug, Default)]
+pub struct DarkBook {
+    buy_orders: Vec<Order<Buy, Open>>,
+    sell_orders: Vec<Order<Sell, Open>>,
+}
+
+impl DarkBook {
+    pub fn new() -> DarkBook {
+        DarkBook::default()
+    }
+
+    pub fn buy_orders(&self) -> &[Order<Buy, Open>] {
+        &self.buy_orders
+    }
+
+    pub fn sell_orders(&self) -> &[Order<Sell, Open>] {
+        &self.sell_orders
+    }
+
+    pub fn len(&self) -> usize {
+        self.buy_orders.len() + self.sell_orders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub(crate) fn push_buy(&mut self, order: Order<Buy, Open>) {
+        self.buy_orders.push(order);
+    }
+
+    pub(crate) fn push_sell(&mut self, order: Order<Sell, Open>) {
+        self.sell_orders.push(order);
+    }
+
+    /// Removes an order, returning whether it was found.
+    pub(crate) fn cancel(&mut self, order_id: &str) -> bool {
+        let count = self.len();
+
+        self.buy_orders.retain(|order| order.id() != order_id);
+        self.sell_orders.retain(|order| order.id() != order_id);
+
+        self.len() < count
+    }
+
+    /// Removes the orders accepted by `filter`, returning their ids.
+    pub(crate) fn cancel_where(
+        &mut self,
+        filter: impl Fn(&str, &OrderInstructions) -> bool,
+    ) -> Vec<(String, Side)> {
+        let mut cancelled = vec![];
+
+        self.buy_orders.retain(|order| {
+            let cancel = filter(order.investor().id(), order.instructions());
+
+            if cancel {
+                cancelled.push((order.id().to_owned(), Side::Buy));
+            }
+
+            !cancel
+        });
+
+        self.sell_orders.retain(|order| {
+            let cancel = filter(order.investor().id(), order.instructions());
+
+            if cancel {
+                cancelled.push((order.id().to_owned(), Side::Sell));
+            }
+
+            !cancel
+        });
+
+        cancelled
+    }
+
+    /// Removes every order, returning how many were removed.
+    pub(crate) fn clear(&mut self) -> usize {
+        let count = self.len();
+
+        self.buy_orders.clear();
+        self.sell_orders.clear();
+
+        count
+    }
+
+    /// Trades the oldest buy and sell orders accepting `midpoint` until one
+    /// of the sides runs out of them. Sell orders whose investor cannot
+    /// deliver the shares are removed.
+    pub(crate) fn cross(
+        &mut self,
+        midpoint: f32,
+        traded_at: DateTime<Utc>,
+    ) -> Vec<Transaction> {
+        let mut transactions = vec![];
+
+        while let (Some(buy_index), Some(sell_index)) = (
+            self.buy_orders
+                .iter()
+                .position(|order| *order.price() >= midpoint),
+            self.sell_orders
+                .iter()
+                .position(|order| *order.price() <= midpoint),
+        ) {
+            let shares = cmp::min(
+                *self.buy_orders[buy_index].pending_shares(),
+                *self.sell_orders[sell_index].pending_shares(),
+            );
+
+            let Ok(sell_order) = self.sell_orders[sell_index].sell(shares)
+            else {
+                self.sell_orders.remove(sell_index);
+                continue;
+            };
+            let Ok(buy_order) = self.buy_orders[buy_index].buy(shares) else {
+                self.buy_orders.remove(buy_index);
+                continue;
+            };
+
+            if let OrderTransition::Closed(_) = buy_order {
+                self.buy_orders.remove(buy_index);
+            }
+
+            if let OrderTransition::Closed(_) = sell_order {
+                self.sell_orders.remove(sell_index);
+            }
+
+            transactions.push(
+                Transaction::new(buy_order, sell_order, shares, midpoint)
+                    .with_traded_at(traded_at)
+                    .with_dark(),
+            );
+        }
+
+        transactions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::entities::{asset::Asset, investor::Investor};
+
+    use super::*;
+
+    #[test]
+    fn cross_at_midpoint() {
+        let asset = Asset::new("HGLG11");
+        let seller =
+            Investor::new("123", "Foo", vec![(asset.id().to_owned(), 100)]);
+        let buyer = Investor::new("321", "Bar", vec![]);
+
+        let mut book = DarkBook::new();
+
+        book.push_buy(Order::new(
+            asset.to_owned(),
+            buyer.to_owned(),
+            "A",
+            5.0,
+            10,
+        ));
+        book.push_buy(Order::new(asset.to_owned(), buyer, "B", 6.0, 10));
+        book.push_sell(Order::new(
+            asset.to_owned(),
+            seller.to_owned(),
+            "C",
+            5.5,
+            15,
+        ));
+        book.push_sell(Order::new(asset.to_owned(), seller, "D", 5.0, 15));
+
+        // "A" and "C" do not accept the midpoint, so "B" trades with "D"
+        let transactions = book.cross(5.25, Utc::now());
+
+        assert_eq!(1, transactions.len());
+        assert_eq!("B", transactions[0].buying_order().get_order_id());
+        assert_eq!("D", transactions[0].selling_order().get_order_id());
+        assert_eq!(10, transactions[0].traded_shares());
+        assert_eq!(5.25, transactions[0].price());
+        assert!(transactions[0].is_dark());
+
+        assert_eq!(3, book.len());
+        assert!(book.cross(4.5, Utc::now()).is_empty());
+    }
+}
@@ -0,0 +1,36 @@
+use serde::Serialize;
+
+use crate::market_data::candles::{Candle, Interval};
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct CandleDTO {
+    asset_id: String,
+    interval: Interval,
+    open_time: String,
+    close_time: String,
+    open: f32,
+    high: f32,
+    low: f32,
+    close: f32,
+    volume: u64,
+    vwap: f32,
+    trade_count: u64,
+}
+
+impl From<Candle> for CandleDTO {
+    fn from(value: Candle) -> Self {
+        CandleDTO {
+            asset_id: value.asset_id().into(),
+            interval: value.interval(),
+            open_time: value.open_time().to_rfc3339(),
+            close_time: value.close_time().to_rfc3339(),
+            open: value.open(),
+            high: value.high(),
+            low: value.low(),
+            close: value.close(),
+            volume: value.volume(),
+            vwap: value.vwap(),
+            trade_count: value.trade_count(),
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use serde::Deserialize;
+
+use crate::{
+    allocation::MatchingPolicy, order_book::SelfTradePrevention,
+    registry::InstrumentStatus,
+};
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct InstrumentDTO {
+    pub id: String,
+    pub name: String,
+    pub currency: String,
+    #[serde(default)]
+    pub status: InstrumentStatus,
+    pub tick_size: Option<f32>,
+    #[serde(default)]
+    pub tick_tiers: Vec<(f32, f32)>,
+    pub round_lot: Option<u32>,
+    pub min_quantity: Option<u32>,
+    pub max_quantity: Option<u32>,
+    pub max_notional: Option<f32>,
+    pub static_band: Option<f32>,
+    pub dynamic_band: Option<f32>,
+    pub self_trade_prevention: Option<SelfTradePrevention>,
+    #[serde(default)]
+    pub odd_lots: bool,
+    #[serde(default)]
+    pub dark_pool: bool,
+    #[serde(default)]
+    pub matching_policy: MatchingPolicy,
+    /// Fractions of the market value margin accounts must cover to open
+    /// and to keep a position, the maintenance one defaulting to it.
+    pub initial_margin: Option<f32>,
+    pub maintenance_margin: Option<f32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserialize_instrument() {
+        let json = r#"{
+            "id": "HGLG11",
+            "name": "CSHG Logística FII",
+            "currency": "BRL",
+            "tick_size": 0.01,
+            "round_lot": 100,
+            "static_band": 20.0,
+            "self_trade_prevention": "CancelNewest",
+            "odd_lots": true,
+            "matching_policy": "ProRata",
+            "initial_margin": 0.5
+        }"#;
+
+        let expected_instrument = InstrumentDTO {
+            id: "HGLG11".into(),
+            name: "CSHG Logística FII".into(),
+            currency: "BRL".into(),
+            status: InstrumentStatus::Active,
+            tick_size: Some(0.01),
+            tick_tiers: vec![],
+            round_lot: Some(100),
+            min_quantity: None,
+            max_quantity: None,
+            max_notional: None,
+            static_band: Some(20.0),
+            dynamic_band: None,
+            self_trade_prevention: Some(SelfTradePrevention::CancelNewest),
+            odd_lots: true,
+            dark_pool: false,
+            matching_policy: MatchingPolicy::ProRata,
+            initial_margin: Some(0.5),
+            maintenance_margin: None,
+        };
+
+        assert_eq!(expected_instrument, serde_json::from_str(json).unwrap())
+    }
+}
@@ -1,2 +1,4 @@
+pub mod candle_dto;
+pub mod instrument_dto;
 pub mod order_dto;
 pub mod transaction_dto;
@@ -1,5 +1,7 @@
 use serde::Deserialize;
 
+use crate::entities::order::{PegReference, PostOnly};
+
 #[derive(Debug, Deserialize, PartialEq)]
 #[serde(tag = "order_type")]
 pub enum IncomingOrderDTO<'a> {
@@ -8,14 +10,38 @@ pub enum IncomingOrderDTO<'a> {
     Sell(OrderDTO<'a>),
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Default, Deserialize, PartialEq)]
 pub struct OrderDTO<'a> {
     pub id: &'a str,
     pub investor_id: &'a str,
     pub investor_name: &'a str,
     pub asset_id: &'a str,
+    /// Limit price, ignored by market and pegged orders.
+    #[serde(default)]
     pub price: f32,
     pub quantity: u32,
+    #[serde(default)]
+    pub market: bool,
+    pub stop_price: Option<f32>,
+    pub trailing_amount: Option<f32>,
+    pub trailing_percentage: Option<f32>,
+    pub display_quantity: Option<u32>,
+    pub post_only: Option<PostOnly>,
+    #[serde(default)]
+    pub all_or_none: bool,
+    /// Smallest execution accepted by the order.
+    pub min_qty: Option<u32>,
+    pub peg: Option<PegReference>,
+    /// Added to the reference price of pegged orders.
+    #[serde(default)]
+    pub peg_offset: f32,
+    #[serde(default)]
+    pub dark: bool,
+    /// Gateway session, whose orders are cancelled when it disconnects.
+    pub session_id: Option<&'a str>,
+    /// Borrows every share sold, against the locates of the investor.
+    #[serde(default)]
+    pub short_sell: bool,
 }
 
 #[cfg(test)]
@@ -41,6 +67,7 @@ mod tests {
             asset_id: "HGLG11",
             price: 13.45,
             quantity: 5,
+            ..Default::default()
         });
 
         assert_eq!(expected_order, serde_json::from_str(json).unwrap())
@@ -65,6 +92,35 @@ mod tests {
             asset_id: "HGLG11",
             price: 13.45,
             quantity: 5,
+            ..Default::default()
+        });
+
+        assert_eq!(expected_order, serde_json::from_str(json).unwrap())
+    }
+
+    #[test]
+    fn deserialize_stop_loss() {
+        let json = r#"{
+            "id": "a16a766e-3373-457b-965a-6aee3c145b4f",
+            "investor_id": "394970b3-52aa-4dfb-8e7d-55e03251ff5c",
+            "investor_name": "Joe Doe",
+            "asset_id": "HGLG11",
+            "quantity": 5,
+            "market": true,
+            "stop_price": 12.9,
+            "order_type": "Sell"
+        }"#;
+
+        let expected_order = IncomingOrderDTO::Sell(OrderDTO {
+            id: "a16a766e-3373-457b-965a-6aee3c145b4f",
+            investor_id: "394970b3-52aa-4dfb-8e7d-55e03251ff5c",
+            investor_name: "Joe Doe",
+            asset_id: "HGLG11",
+            price: 0.0,
+            quantity: 5,
+            market: true,
+            stop_price: Some(12.9),
+            ..Default::default()
         });
 
         assert_eq!(expected_order, serde_json::from_str(json).unwrap())
@@ -10,6 +10,9 @@ pub struct TransactionDTO {
     traded_shares: u32,
     total: f32,
     traded_at: String,
+    dark: bool,
+    short_sale: bool,
+    short_shares: u32,
 }
 
 impl From<Transaction> for TransactionDTO {
@@ -21,6 +24,9 @@ impl From<Transaction> for TransactionDTO {
             traded_shares: value.traded_shares(),
             total: value.total(),
             traded_at: value.traded_at().to_rfc3339(),
+            dark: value.is_dark(),
+            short_sale: value.is_short_sale(),
+            short_shares: value.short_shares(),
         }
     }
 }
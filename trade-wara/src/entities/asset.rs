@@ -3,6 +3,10 @@ pub struct Asset {
     id: String,
 }
 
+/// B3 trades odd lots in a separate fractional market, identified by the
+/// ticker of the asset followed by this suffix.
+pub const FRACTIONAL_SUFFIX: char = 'F';
+
 impl Asset {
     pub fn new(id: &str) -> Self {
         Asset { id: id.to_string() }
@@ -11,4 +15,248 @@ impl Asset {
     pub fn id(&self) -> &str {
         &self.id
     }
+
+    pub fn is_fractional(&self) -> bool {
+        self.id.ends_with(FRACTIONAL_SUFFIX)
+    }
+
+    /// Asset of the fractional market for odd lots of this one.
+    pub fn fractional(&self) -> Asset {
+        if self.is_fractional() {
+            return self.to_owned();
+        }
+
+        Asset::new(&format!("{}{}", self.id, FRACTIONAL_SUFFIX))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ReferenceDataError {
+    InvalidTickSize(f32),
+    InvalidLotSize(u32),
+    QuantityBelowMinimum(u32),
+    QuantityAboveMaximum(u32),
+    NotionalAboveMaximum(f32),
+}
+
+/// Trading rules of an asset. Tick sizes may be tiered by price, each tier
+/// starting at its price and lasting until the next one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReferenceData {
+    tick_sizes: Vec<(f32, f32)>,
+    round_lot: u32,
+    min_quantity: u32,
+    max_quantity: Option<u32>,
+    max_notional: Option<f32>,
+}
+
+impl Default for ReferenceData {
+    fn default() -> Self {
+        ReferenceData {
+            tick_sizes: vec![(0.0, 0.01)],
+            round_lot: 1,
+            min_quantity: 1,
+            max_quantity: None,
+            max_notional: None,
+        }
+    }
+}
+
+impl ReferenceData {
+    pub fn new() -> ReferenceData {
+        ReferenceData::default()
+    }
+
+    pub fn with_tick_size(mut self, tick_size: f32) -> ReferenceData {
+        self.tick_sizes = vec![(0.0, tick_size)];
+        self
+    }
+
+    /// Uses `tick_size` for prices from `from_price` up to the next tier.
+    pub fn with_tick_tier(
+        mut self,
+        from_price: f32,
+        tick_size: f32,
+    ) -> ReferenceData {
+        self.tick_sizes.retain(|(price, _)| *price != from_price);
+        self.tick_sizes.push((from_price, tick_size));
+        self.tick_sizes.sort_by(|a, b| a.0.total_cmp(&b.0));
+        self
+    }
+
+    pub fn with_round_lot(mut self, round_lot: u32) -> ReferenceData {
+        self.round_lot = round_lot.max(1);
+        self
+    }
+
+    pub fn with_min_quantity(mut self, min_quantity: u32) -> ReferenceData {
+        self.min_quantity = min_quantity;
+        self
+    }
+
+    pub fn with_max_quantity(mut self, max_quantity: u32) -> ReferenceData {
+        self.max_quantity = Some(max_quantity);
+        self
+    }
+
+    pub fn with_max_notional(mut self, max_notional: f32) -> ReferenceData {
+        self.max_notional = Some(max_notional);
+        self
+    }
+
+    /// Rules of the fractional market, trading quantities below a round lot.
+    pub fn fractional(&self) -> ReferenceData {
+        ReferenceData {
+            tick_sizes: self.tick_sizes.to_owned(),
+            round_lot: 1,
+            min_quantity: 1,
+            max_quantity: Some(self.round_lot.saturating_sub(1).max(1)),
+            max_notional: self.max_notional,
+        }
+    }
+
+    pub fn tick_size(&self, price: f32) -> f32 {
+        self.tick_sizes
+            .iter()
+            .take_while(|(from_price, _)| *from_price <= price)
+            .last()
+            .or(self.tick_sizes.first())
+            .map(|(_, tick_size)| *tick_size)
+            .unwrap_or(0.01)
+    }
+
+    pub fn round_lot(&self) -> u32 {
+        self.round_lot
+    }
+
+    pub fn is_round_lot(&self, shares: u32) -> bool {
+        shares.is_multiple_of(self.round_lot)
+    }
+
+    pub fn validate(
+        &self,
+        price: f32,
+        shares: u32,
+    ) -> Result<(), ReferenceDataError> {
+        let tick_size = self.tick_size(price) as f64;
+        let off_tick =
+            price as f64 - (price as f64 / tick_size).round() * tick_size;
+
+        // Tolerates the rounding of decimal prices into floats
+        if off_tick.abs() > price.abs() as f64 * 1e-6 {
+            return Err(ReferenceDataError::InvalidTickSize(tick_size as f32));
+        }
+
+        self.validate_shares(shares)?;
+
+        if let Some(max_notional) = self.max_notional {
+            if price * shares as f32 > max_notional {
+                return Err(ReferenceDataError::NotionalAboveMaximum(
+                    max_notional,
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks only the quantity rules, for orders trading at any price.
+    pub fn validate_shares(
+        &self,
+        shares: u32,
+    ) -> Result<(), ReferenceDataError> {
+        if shares < self.min_quantity {
+            return Err(ReferenceDataError::QuantityBelowMinimum(
+                self.min_quantity,
+            ));
+        }
+
+        if let Some(max_quantity) = self.max_quantity {
+            if shares > max_quantity {
+                return Err(ReferenceDataError::QuantityAboveMaximum(
+                    max_quantity,
+                ));
+            }
+        }
+
+        if !self.is_round_lot(shares) {
+            return Err(ReferenceDataError::InvalidLotSize(self.round_lot));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fractional_asset() {
+        let asset = Asset::new("HGLG11");
+
+        assert!(!asset.is_fractional());
+        assert_eq!("HGLG11F", asset.fractional().id());
+        assert_eq!("HGLG11F", asset.fractional().fractional().id());
+    }
+
+    #[test]
+    fn tiered_tick_sizes() {
+        let reference = ReferenceData::new()
+            .with_tick_size(0.001)
+            .with_tick_tier(1.0, 0.01)
+            .with_tick_tier(100.0, 0.05);
+
+        assert_eq!(0.001, reference.tick_size(0.5));
+        assert_eq!(0.01, reference.tick_size(1.0));
+        assert_eq!(0.05, reference.tick_size(150.0));
+
+        assert_eq!(Ok(()), reference.validate(0.123, 1));
+        assert_eq!(Ok(()), reference.validate(13.45, 1));
+        assert_eq!(Ok(()), reference.validate(150.05, 1));
+        assert_eq!(
+            Err(ReferenceDataError::InvalidTickSize(0.01)),
+            reference.validate(13.455, 1)
+        );
+        assert_eq!(
+            Err(ReferenceDataError::InvalidTickSize(0.05)),
+            reference.validate(150.01, 1)
+        );
+    }
+
+    #[test]
+    fn validate_quantities() {
+        let reference = ReferenceData::new()
+            .with_round_lot(100)
+            .with_min_quantity(100)
+            .with_max_quantity(10_000)
+            .with_max_notional(50_000.0);
+
+        assert_eq!(Ok(()), reference.validate(10.0, 500));
+        assert_eq!(
+            Err(ReferenceDataError::QuantityBelowMinimum(100)),
+            reference.validate(10.0, 50)
+        );
+        assert_eq!(
+            Err(ReferenceDataError::InvalidLotSize(100)),
+            reference.validate(10.0, 150)
+        );
+        assert_eq!(
+            Err(ReferenceDataError::QuantityAboveMaximum(10_000)),
+            reference.validate(1.0, 20_000)
+        );
+        assert_eq!(
+            Err(ReferenceDataError::NotionalAboveMaximum(50_000.0)),
+            reference.validate(10.0, 6_000)
+        );
+
+        // "Odd lots should be accepted by the fractional market only"
+        let fractional = reference.fractional();
+
+        assert_eq!(Ok(()), fractional.validate(10.0, 50));
+        assert_eq!(
+            Err(ReferenceDataError::QuantityAboveMaximum(99)),
+            fractional.validate(10.0, 100)
+        );
+    }
 }
@@ -5,6 +5,7 @@ pub struct Investor {
     id: String,
     name: String,
     assets: HashMap<String, u32>,
+    short_assets: HashMap<String, u32>,
 }
 
 impl Investor {
@@ -13,16 +14,49 @@ impl Investor {
             id: id.into(),
             name: name.into(),
             assets: HashMap::from_iter(assets),
+            short_assets: HashMap::new(),
         }
     }
 
-    pub fn increment_asset(&mut self, asset_id: &str, quantity: u32) {
+    /// Adds shares to the holdings, covering any short position first.
+    pub fn increment_asset(&mut self, asset_id: &str, mut quantity: u32) {
+        if let Some(short) = self.short_assets.get_mut(asset_id) {
+            let covered = quantity.min(*short);
+
+            *short -= covered;
+            quantity -= covered;
+
+            if *short == 0 {
+                self.short_assets.remove(asset_id);
+            }
+
+            if quantity == 0 {
+                return;
+            }
+        }
+
         self.assets
             .entry(asset_id.into())
             .and_modify(|current| *current += quantity)
             .or_insert(quantity);
     }
 
+    /// Sells the shares held first, the rest are borrowed and owed as a
+    /// short position.
+    pub fn sell_short(&mut self, asset_id: &str, quantity: u32) {
+        let held = self.assets.get(asset_id).copied().unwrap_or(0);
+        let sold = quantity.min(held);
+
+        if sold > 0 {
+            self.assets.insert(asset_id.into(), held - sold);
+        }
+
+        if quantity > sold {
+            *self.short_assets.entry(asset_id.into()).or_insert(0) +=
+                quantity - sold;
+        }
+    }
+
     pub fn decrement_asset(
         &mut self,
         asset_id: &str,
@@ -41,9 +75,22 @@ impl Investor {
         Ok(())
     }
 
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
     pub fn assets(&self) -> &HashMap<String, u32> {
         &self.assets
     }
+
+    /// Borrowed shares owed by the investor.
+    pub fn short_position(&self, asset_id: &str) -> u32 {
+        self.short_assets.get(asset_id).copied().unwrap_or(0)
+    }
 }
 
 #[cfg(test)]
@@ -90,4 +137,26 @@ mod tests {
         assert_eq!(investor.assets["HGLG11"], 3);
         assert_eq!(investor.assets.len(), 1);
     }
+
+    #[test]
+    fn sell_investor_assets_short() {
+        let mut investor =
+            Investor::new("123", "Joe", vec![("HGLG11".into(), 10)]);
+
+        investor.sell_short("HGLG11", 25);
+
+        assert_eq!(investor.assets["HGLG11"], 0);
+        assert_eq!(15, investor.short_position("HGLG11"));
+
+        // "Buying should cover the short position before adding holdings"
+        investor.increment_asset("HGLG11", 10);
+
+        assert_eq!(5, investor.short_position("HGLG11"));
+        assert_eq!(investor.assets["HGLG11"], 0);
+
+        investor.increment_asset("HGLG11", 8);
+
+        assert_eq!(0, investor.short_position("HGLG11"));
+        assert_eq!(investor.assets["HGLG11"], 3);
+    }
 }
@@ -1,30 +1,52 @@
 use std::{
     any::{Any, TypeId},
     marker::PhantomData,
+    sync::atomic::{self, AtomicU64},
 };
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    dto::order_dto::IncomingOrderDTO, entities::asset::Asset,
-    entities::investor::Investor, ComparableFloat,
+    dto::order_dto::{IncomingOrderDTO, OrderDTO},
+    entities::asset::Asset,
+    entities::investor::Investor,
+    ComparableFloat,
 };
 
+/// Source of the time priority of new orders.
+static NEXT_SEQUENCE: AtomicU64 = AtomicU64::new(1);
+
+fn next_sequence() -> u64 {
+    NEXT_SEQUENCE.fetch_add(1, atomic::Ordering::Relaxed)
+}
+
 pub trait OrderItem: Sync + Send {
     fn resolve_type(&self) -> OrderResolution;
 
     fn asset_id(&self) -> &str;
 }
-pub trait OrderType: Sync + Send + PartialEq + Eq {}
+pub trait OrderType: Sync + Send + PartialEq + Eq {
+    const SIDE: Side;
+}
 pub trait OrderState: Sync + Send + PartialEq + Eq {}
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Ord, PartialOrd)]
 pub struct Buy;
-impl OrderType for Buy {}
+impl OrderType for Buy {
+    const SIDE: Side = Side::Buy;
+}
 
 #[derive(Debug, Clone, PartialEq, Eq, Ord, PartialOrd)]
 pub struct Sell;
-impl OrderType for Sell {}
+impl OrderType for Sell {
+    const SIDE: Side = Side::Sell;
+}
 
 #[derive(Debug, Clone, PartialEq, Eq, Ord, PartialOrd)]
 pub struct Open;
@@ -40,12 +62,344 @@ pub struct Order<T: OrderType, S: OrderState> {
     price: ComparableFloat,
     shares: u32,
     pending_shares: u32,
+    reserve_shares: u32,
     asset: Asset,
     investor: Investor,
+    sequence: u64,
+    instructions: OrderInstructions,
     state: PhantomData<S>,
     order_type: PhantomData<T>,
 }
 
+/// How an order is handled besides resting at its limit price.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OrderInstructions {
+    market: bool,
+    stop_price: Option<ComparableFloat>,
+    trailing: Option<TrailingStop>,
+    display_quantity: Option<u32>,
+    post_only: Option<PostOnly>,
+    all_or_none: bool,
+    min_quantity: Option<u32>,
+    peg: Option<Peg>,
+    dark: bool,
+    session_id: Option<String>,
+    short_sell: bool,
+}
+
+/// What happens to a post-only order that would trade on arrival.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum PostOnly {
+    Reject,
+    /// Moves the order one tick away from the best opposite price.
+    Reprice,
+}
+
+/// Best price of the book a pegged order follows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum PegReference {
+    /// Halfway between the best bid and ask.
+    Midpoint,
+    /// Best price of the side of the order.
+    Primary,
+}
+
+/// Price derived from the best lit orders, ignoring other pegged orders.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Peg {
+    reference: PegReference,
+    offset: ComparableFloat,
+}
+
+impl Peg {
+    pub fn reference(&self) -> PegReference {
+        self.reference
+    }
+
+    /// Amount added to the reference price, whatever the side.
+    pub fn offset(&self) -> f32 {
+        *self.offset
+    }
+
+    /// Price of the peg from the best lit prices, if they are available.
+    pub fn price(
+        &self,
+        side: Side,
+        bid: Option<f32>,
+        ask: Option<f32>,
+    ) -> Option<f32> {
+        let reference = match (self.reference, side) {
+            (PegReference::Midpoint, _) => (bid? + ask?) / 2.0,
+            (PegReference::Primary, Side::Buy) => bid?,
+            (PegReference::Primary, Side::Sell) => ask?,
+        };
+
+        Some(reference + *self.offset)
+    }
+}
+
+/// Distance kept between the stop price and the best price traded since the
+/// order was placed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrailingOffset {
+    Amount(ComparableFloat),
+    Percentage(ComparableFloat),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrailingStop {
+    offset: TrailingOffset,
+    watermark: Option<ComparableFloat>,
+}
+
+impl TrailingStop {
+    pub fn offset(&self) -> TrailingOffset {
+        self.offset
+    }
+
+    /// Highest price traded for sell orders, lowest for buy orders.
+    pub fn watermark(&self) -> Option<f32> {
+        self.watermark.as_deref().copied()
+    }
+
+    /// Moves the watermark with `price`, returning the resulting stop price.
+    fn track(&mut self, side: Side, price: f32) -> f32 {
+        let watermark = match (side, self.watermark()) {
+            (_, None) => price,
+            (Side::Sell, Some(watermark)) => watermark.max(price),
+            (Side::Buy, Some(watermark)) => watermark.min(price),
+        };
+
+        self.watermark = Some(watermark.into());
+
+        let distance = match self.offset {
+            TrailingOffset::Amount(amount) => *amount,
+            TrailingOffset::Percentage(percentage) => {
+                watermark * *percentage / 100.0
+            }
+        };
+
+        match side {
+            Side::Sell => watermark - distance,
+            Side::Buy => watermark + distance,
+        }
+    }
+}
+
+impl OrderInstructions {
+    pub fn new() -> OrderInstructions {
+        OrderInstructions::default()
+    }
+
+    /// Trades at any price, shares left unfilled are cancelled instead of
+    /// resting on the book.
+    pub fn with_market_price(mut self) -> OrderInstructions {
+        self.market = true;
+        self
+    }
+
+    /// Keeps the order out of the book until the last traded price reaches
+    /// `stop_price`, rising for buy orders and falling for sell orders.
+    pub fn with_stop_price(mut self, stop_price: f32) -> OrderInstructions {
+        self.stop_price = Some(stop_price.into());
+        self
+    }
+
+    /// Stop order whose stop price follows the traded prices by `amount`.
+    pub fn with_trailing_amount(mut self, amount: f32) -> OrderInstructions {
+        self.trailing = Some(TrailingStop {
+            offset: TrailingOffset::Amount(amount.into()),
+            watermark: None,
+        });
+        self
+    }
+
+    /// Stop order whose stop price follows the traded prices by a
+    /// `percentage` of the watermark.
+    pub fn with_trailing_percentage(
+        mut self,
+        percentage: f32,
+    ) -> OrderInstructions {
+        self.trailing = Some(TrailingStop {
+            offset: TrailingOffset::Percentage(percentage.into()),
+            watermark: None,
+        });
+        self
+    }
+
+    /// Iceberg order showing at most `display_quantity` shares at a time,
+    /// keeping the rest as a hidden reserve.
+    pub fn with_display_quantity(
+        mut self,
+        display_quantity: u32,
+    ) -> OrderInstructions {
+        self.display_quantity = Some(display_quantity);
+        self
+    }
+
+    /// Only adds liquidity, the order never trades on arrival.
+    pub fn with_post_only(mut self, post_only: PostOnly) -> OrderInstructions {
+        self.post_only = Some(post_only);
+        self
+    }
+
+    /// Trades only when every pending share can be filled at once.
+    pub fn with_all_or_none(mut self) -> OrderInstructions {
+        self.all_or_none = true;
+        self
+    }
+
+    /// Trades only in executions of at least `min_quantity` shares, or of
+    /// every pending share once fewer are left.
+    pub fn with_min_quantity(mut self, min_quantity: u32) -> OrderInstructions {
+        self.min_quantity = Some(min_quantity);
+        self
+    }
+
+    /// Follows the best lit prices instead of resting at a limit price.
+    pub fn with_peg(
+        mut self,
+        reference: PegReference,
+        offset: f32,
+    ) -> OrderInstructions {
+        self.peg = Some(Peg {
+            reference,
+            offset: offset.into(),
+        });
+        self
+    }
+
+    /// Rests in the dark book of the asset instead of the lit one.
+    pub fn with_dark(mut self) -> OrderInstructions {
+        self.dark = true;
+        self
+    }
+
+    /// Session the order was sent through, cancelled on its disconnection.
+    pub fn with_session_id(mut self, session_id: &str) -> OrderInstructions {
+        self.session_id = Some(session_id.into());
+        self
+    }
+
+    /// Sell order allowed to sell more shares than the investor holds,
+    /// borrowing the rest.
+    pub fn with_short_sell(mut self) -> OrderInstructions {
+        self.short_sell = true;
+        self
+    }
+
+    pub fn is_market(&self) -> bool {
+        self.market
+    }
+
+    pub fn post_only(&self) -> Option<PostOnly> {
+        self.post_only
+    }
+
+    pub fn is_all_or_none(&self) -> bool {
+        self.all_or_none
+    }
+
+    pub fn min_quantity(&self) -> Option<u32> {
+        self.min_quantity
+    }
+
+    /// All-or-none and minimum quantity orders, which cannot take any
+    /// execution they are offered.
+    pub fn is_constrained(&self) -> bool {
+        self.all_or_none || self.min_quantity.is_some()
+    }
+
+    pub fn peg(&self) -> Option<Peg> {
+        self.peg
+    }
+
+    pub fn is_dark(&self) -> bool {
+        self.dark
+    }
+
+    pub fn session_id(&self) -> Option<&str> {
+        self.session_id.as_deref()
+    }
+
+    pub fn is_short_sell(&self) -> bool {
+        self.short_sell
+    }
+
+    pub fn display_quantity(&self) -> Option<u32> {
+        self.display_quantity
+    }
+
+    /// Whether the order waits outside the book for its stop price.
+    pub fn is_stop(&self) -> bool {
+        self.stop_price.is_some() || self.trailing.is_some()
+    }
+
+    pub fn trailing(&self) -> Option<&TrailingStop> {
+        self.trailing.as_ref()
+    }
+
+    pub fn stop_price(&self) -> Option<f32> {
+        self.stop_price.as_deref().copied()
+    }
+}
+
+impl From<&OrderDTO<'_>> for OrderInstructions {
+    fn from(value: &OrderDTO) -> OrderInstructions {
+        let mut instructions = OrderInstructions::new();
+
+        if value.market {
+            instructions = instructions.with_market_price();
+        }
+
+        if let Some(stop_price) = value.stop_price {
+            instructions = instructions.with_stop_price(stop_price);
+        }
+
+        if let Some(amount) = value.trailing_amount {
+            instructions = instructions.with_trailing_amount(amount);
+        }
+
+        if let Some(percentage) = value.trailing_percentage {
+            instructions = instructions.with_trailing_percentage(percentage);
+        }
+
+        if let Some(display_quantity) = value.display_quantity {
+            instructions = instructions.with_display_quantity(display_quantity);
+        }
+
+        if let Some(post_only) = value.post_only {
+            instructions = instructions.with_post_only(post_only);
+        }
+
+        if value.all_or_none {
+            instructions = instructions.with_all_or_none();
+        }
+
+        if let Some(min_quantity) = value.min_qty {
+            instructions = instructions.with_min_quantity(min_quantity);
+        }
+
+        if let Some(reference) = value.peg {
+            instructions = instructions.with_peg(reference, value.peg_offset);
+        }
+
+        if value.dark {
+            instructions = instructions.with_dark();
+        }
+
+        if let Some(session_id) = value.session_id {
+            instructions = instructions.with_session_id(session_id);
+        }
+
+        if value.short_sell {
+            instructions = instructions.with_short_sell();
+        }
+
+        instructions
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum OrderError {
     OutRangeShareCount,
@@ -77,8 +431,11 @@ impl<T: OrderType, S: OrderState> Order<T, S> {
             price: price.into(),
             shares,
             pending_shares: shares,
+            reserve_shares: 0,
             asset,
             investor,
+            sequence: next_sequence(),
+            instructions: OrderInstructions::default(),
             state: PhantomData,
             order_type: PhantomData,
         }
@@ -90,8 +447,11 @@ impl<T: OrderType, S: OrderState> Order<T, S> {
             price: self.price.to_owned(),
             shares: self.shares.to_owned(),
             pending_shares: self.pending_shares,
+            reserve_shares: self.reserve_shares,
             asset: self.asset.to_owned(),
             investor: self.investor.to_owned(),
+            sequence: self.sequence,
+            instructions: self.instructions.to_owned(),
             state: PhantomData,
             order_type: PhantomData,
         }
@@ -103,15 +463,18 @@ impl<T: OrderType, S: OrderState> Order<T, S> {
             price: self.price.to_owned(),
             shares: self.shares.to_owned(),
             pending_shares: self.pending_shares,
+            reserve_shares: self.reserve_shares,
             asset: self.asset.to_owned(),
             investor: self.investor.to_owned(),
+            sequence: self.sequence,
+            instructions: self.instructions.to_owned(),
             state: PhantomData,
             order_type: PhantomData,
         }
     }
 
     pub fn check_order(&self) -> OrderTransition<T> {
-        if self.pending_shares > 0 {
+        if self.pending_shares > 0 || self.reserve_shares > 0 {
             return OrderTransition::Open(self.copy());
         }
 
@@ -133,12 +496,118 @@ impl<T: OrderType, S: OrderState> Order<T, S> {
     pub fn asset(&self) -> &Asset {
         &self.asset
     }
+
+    /// Time priority of the order, lower values were created first.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Market orders take the most aggressive price of their side, so they
+    /// rank ahead of every limit order.
+    pub fn with_instructions(
+        mut self,
+        instructions: OrderInstructions,
+    ) -> Order<T, S> {
+        if let Some(display_quantity) = instructions.display_quantity() {
+            let shares = self.pending_shares + self.reserve_shares;
+
+            self.pending_shares = shares.min(display_quantity.max(1));
+            self.reserve_shares = shares - self.pending_shares;
+        }
+
+        if instructions.is_market() {
+            self.price = match T::SIDE {
+                Side::Buy => f32::MAX,
+                Side::Sell => 0.0,
+            }
+            .into();
+        }
+
+        self.instructions = instructions;
+        self
+    }
+
+    pub fn instructions(&self) -> &OrderInstructions {
+        &self.instructions
+    }
+
+    /// Hidden shares of an iceberg order, not counted as pending.
+    pub fn reserve_shares(&self) -> u32 {
+        self.reserve_shares
+    }
+
+    /// Shows a new peak of an iceberg order once the last one is filled,
+    /// losing its time priority. Returns whether the order was replenished.
+    pub(crate) fn replenish(&mut self) -> bool {
+        if self.pending_shares > 0 || self.reserve_shares == 0 {
+            return false;
+        }
+
+        let display_quantity = self
+            .instructions
+            .display_quantity
+            .unwrap_or(self.reserve_shares);
+
+        self.pending_shares = self.reserve_shares.min(display_quantity.max(1));
+        self.reserve_shares -= self.pending_shares;
+        self.sequence = next_sequence();
+
+        true
+    }
+
+    /// Moves a post-only order to a price where it does not trade.
+    pub(crate) fn reprice(&mut self, price: f32) {
+        self.price = price.into();
+    }
+
+    /// Moves a pegged order to the new price of its peg, ranked behind the
+    /// orders already resting at that price.
+    pub(crate) fn repeg(&mut self, price: f32) {
+        self.price = price.into();
+        self.sequence = next_sequence();
+    }
+
+    /// Turns a triggered stop into a market or limit order, ranked behind the
+    /// orders already resting at its price.
+    pub(crate) fn trigger_stop(&mut self) {
+        self.instructions.stop_price = None;
+        self.instructions.trailing = None;
+        self.sequence = next_sequence();
+    }
+
+    /// Moves the stop price of a trailing stop after a trade at `price`.
+    pub(crate) fn track_price(&mut self, price: f32) {
+        if let Some(trailing) = self.instructions.trailing.as_mut() {
+            let stop_price = trailing.track(T::SIDE, price);
+            self.instructions.stop_price = Some(stop_price.into());
+        }
+    }
 }
 
 impl<T: OrderType> Order<T, Open> {
     pub fn pending_shares(&self) -> &u32 {
         &self.pending_shares
     }
+
+    /// Smallest execution the order accepts.
+    pub fn min_execution(&self) -> u32 {
+        self.instructions
+            .min_quantity
+            .unwrap_or(1)
+            .min(self.pending_shares)
+    }
+
+    /// Removes shares from the order without trading them, the order must
+    /// keep at least one pending share to stay open.
+    pub fn reduce(&mut self, share_count: u32) -> Result<(), OrderError> {
+        if share_count == 0 || self.pending_shares <= share_count {
+            return Err(OrderError::OutRangeShareCount);
+        }
+
+        self.pending_shares -= share_count;
+
+        Ok(())
+    }
 }
 
 impl Order<Buy, Open> {
@@ -167,7 +636,9 @@ impl Order<Sell, Open> {
             return Err(OrderError::OutRangeShareCount);
         }
 
-        if let Err(_) =
+        if self.instructions.short_sell {
+            self.investor.sell_short(self.asset.id(), share_count);
+        } else if let Err(_) =
             self.investor.decrement_asset(self.asset.id(), share_count)
         {
             return Err(OrderError::OutRangeShareCount);
@@ -179,13 +650,150 @@ impl Order<Sell, Open> {
     }
 }
 
+impl OrderResolution {
+    pub fn side(&self) -> Side {
+        match self {
+            OrderResolution::Sell(_) => Side::Sell,
+            OrderResolution::Buy(_) => Side::Buy,
+        }
+    }
+
+    pub fn order_id(&self) -> &str {
+        match self {
+            OrderResolution::Sell(order) => order.get_order_id(),
+            OrderResolution::Buy(order) => order.get_order_id(),
+        }
+    }
+
+    pub fn instructions(&self) -> &OrderInstructions {
+        match self {
+            OrderResolution::Sell(order) => order.instructions(),
+            OrderResolution::Buy(order) => order.instructions(),
+        }
+    }
+
+    pub fn investor_id(&self) -> &str {
+        self.investor().id()
+    }
+
+    pub fn investor(&self) -> &Investor {
+        match self {
+            OrderResolution::Sell(order) => order.investor(),
+            OrderResolution::Buy(order) => order.investor(),
+        }
+    }
+
+    pub fn asset_id(&self) -> &str {
+        match self {
+            OrderResolution::Sell(order) => order.asset_id(),
+            OrderResolution::Buy(order) => order.asset_id(),
+        }
+    }
+
+    pub fn price(&self) -> f32 {
+        match self {
+            OrderResolution::Sell(order) => order.price(),
+            OrderResolution::Buy(order) => order.price(),
+        }
+    }
+
+    /// Shares left to trade, including the reserve of an iceberg order.
+    pub fn shares(&self) -> u32 {
+        match self {
+            OrderResolution::Sell(order) => order.shares(),
+            OrderResolution::Buy(order) => order.shares(),
+        }
+    }
+
+    /// Whether a stop order is triggered by a trade at `last_price`.
+    pub fn is_stop_triggered(&self, last_price: f32) -> bool {
+        match (self.side(), self.instructions().stop_price()) {
+            (Side::Buy, Some(stop_price)) => last_price >= stop_price,
+            (Side::Sell, Some(stop_price)) => last_price <= stop_price,
+            (_, None) => false,
+        }
+    }
+
+    pub(crate) fn trigger_stop(&mut self) {
+        match self {
+            OrderResolution::Sell(order) => order.trigger_stop(),
+            OrderResolution::Buy(order) => order.trigger_stop(),
+        }
+    }
+
+    pub(crate) fn track_price(&mut self, price: f32) {
+        match self {
+            OrderResolution::Sell(order) => order.track_price(price),
+            OrderResolution::Buy(order) => order.track_price(price),
+        }
+    }
+}
+
 impl<T: OrderType> OrderTransition<T> {
+    pub fn instructions(&self) -> &OrderInstructions {
+        match self {
+            OrderTransition::Open(order) => order.instructions(),
+            OrderTransition::Closed(order) => order.instructions(),
+        }
+    }
+
+    pub(crate) fn trigger_stop(&mut self) {
+        match self {
+            OrderTransition::Open(order) => order.trigger_stop(),
+            OrderTransition::Closed(order) => order.trigger_stop(),
+        }
+    }
+
+    pub(crate) fn track_price(&mut self, price: f32) {
+        match self {
+            OrderTransition::Open(order) => order.track_price(price),
+            OrderTransition::Closed(order) => order.track_price(price),
+        }
+    }
+
     pub fn get_order_id(&self) -> &str {
         match self {
             OrderTransition::Open(order) => order.id(),
             OrderTransition::Closed(order) => order.id(),
         }
     }
+
+    pub fn asset_id(&self) -> &str {
+        match self {
+            OrderTransition::Open(order) => order.asset().id(),
+            OrderTransition::Closed(order) => order.asset().id(),
+        }
+    }
+
+    pub fn investor_id(&self) -> &str {
+        self.investor().id()
+    }
+
+    pub fn investor(&self) -> &Investor {
+        match self {
+            OrderTransition::Open(order) => order.investor(),
+            OrderTransition::Closed(order) => order.investor(),
+        }
+    }
+
+    pub fn price(&self) -> f32 {
+        match self {
+            OrderTransition::Open(order) => *order.price(),
+            OrderTransition::Closed(order) => *order.price(),
+        }
+    }
+
+    /// Shares left to trade, including the reserve of an iceberg order.
+    pub fn shares(&self) -> u32 {
+        match self {
+            OrderTransition::Open(order) => {
+                order.pending_shares + order.reserve_shares
+            }
+            OrderTransition::Closed(order) => {
+                order.pending_shares + order.reserve_shares
+            }
+        }
+    }
 }
 
 impl<T: OrderType + 'static, S: OrderState> OrderItem for Order<T, S> {
@@ -206,9 +814,14 @@ impl<T: OrderType + 'static, S: OrderState> OrderItem for Order<T, S> {
     }
 }
 
+/// Orders are ranked by price, then the oldest order of the same price ranks
+/// first within the heap of its side.
 impl<T: OrderType, S: OrderState> Ord for Order<T, S> {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.price.cmp(&other.price)
+        self.price.cmp(&other.price).then_with(|| match T::SIDE {
+            Side::Buy => other.sequence.cmp(&self.sequence),
+            Side::Sell => self.sequence.cmp(&other.sequence),
+        })
     }
 }
 
@@ -231,31 +844,44 @@ impl<'a> From<IncomingOrderDTO<'a>> for OrderResolution {
     fn from(value: IncomingOrderDTO) -> OrderResolution {
         match value {
             IncomingOrderDTO::Buy(order) => {
-                OrderResolution::Buy(OrderTransition::Open(Order::new(
-                    Asset::new(order.asset_id),
-                    Investor::new(
-                        order.investor_id,
-                        order.investor_name,
-                        vec![],
-                    ),
-                    order.id,
-                    order.price,
-                    order.quantity,
-                )))
+                OrderResolution::Buy(OrderTransition::Open(
+                    Order::new(
+                        Asset::new(order.asset_id),
+                        Investor::new(
+                            order.investor_id,
+                            order.investor_name,
+                            vec![],
+                        ),
+                        order.id,
+                        order.price,
+                        order.quantity,
+                    )
+                    .with_instructions((&order).into()),
+                ))
             }
 
             IncomingOrderDTO::Sell(order) => {
-                OrderResolution::Sell(OrderTransition::Open(Order::new(
-                    Asset::new(order.asset_id),
-                    Investor::new(
-                        order.investor_id,
-                        order.investor_name,
-                        vec![(order.asset_id.into(), order.quantity)],
-                    ),
-                    order.id,
-                    order.price,
-                    order.quantity,
-                )))
+                // Holdings are not known to the service, so plain sales are
+                // assumed covered and short sales borrow every share
+                let holdings = match order.short_sell {
+                    true => vec![],
+                    false => vec![(order.asset_id.into(), order.quantity)],
+                };
+
+                OrderResolution::Sell(OrderTransition::Open(
+                    Order::new(
+                        Asset::new(order.asset_id),
+                        Investor::new(
+                            order.investor_id,
+                            order.investor_name,
+                            holdings,
+                        ),
+                        order.id,
+                        order.price,
+                        order.quantity,
+                    )
+                    .with_instructions((&order).into()),
+                ))
             }
         }
     }
@@ -301,6 +927,41 @@ mod tests {
         assert_eq!(Ordering::Less, small_order.cmp(&high_order));
     }
 
+    #[test]
+    fn time_priority() {
+        let asset = Asset::new("HGLG11");
+        let investor = Investor::new("123", "Joe", vec![("HGLG11".into(), 10)]);
+
+        let order = |id| {
+            (
+                Order::<Buy, Open>::new(
+                    asset.to_owned(),
+                    investor.to_owned(),
+                    id,
+                    5.0,
+                    1,
+                ),
+                Order::<Sell, Open>::new(
+                    asset.to_owned(),
+                    investor.to_owned(),
+                    id,
+                    5.0,
+                    1,
+                ),
+            )
+        };
+
+        let (old_buy, old_sell) = order("1");
+        let (new_buy, new_sell) = order("2");
+
+        assert!(old_buy.sequence() < new_buy.sequence());
+
+        // "The oldest order of a price should be first on both heaps"
+        assert_eq!(Ordering::Greater, old_buy.cmp(&new_buy));
+        assert_eq!(Ordering::Less, old_sell.cmp(&new_sell));
+        assert_eq!(old_buy.sequence(), old_buy.copy::<Closed>().sequence());
+    }
+
     #[test]
     fn heap_orders() {
         let asset = Asset::new("HGLG11");
@@ -395,4 +1056,98 @@ mod tests {
         assert_eq!(Ok(OrderTransition::Closed(order.copy())), buy_remain);
         assert_eq!(15, order.investor.assets()["HGLG11"]);
     }
+
+    #[test]
+    fn check_reduce() {
+        let asset = Asset::new("HGLG11");
+        let investor = Investor::new("123", "Joe", vec![]);
+
+        let mut order =
+            Order::<Buy, Open>::new(asset, investor.to_owned(), "123", 7.0, 5);
+
+        // "Reducing all pending shares should return an Err"
+        assert_eq!(Err(OrderError::OutRangeShareCount), order.reduce(5));
+        assert_eq!(Err(OrderError::OutRangeShareCount), order.reduce(0));
+
+        // "Reducing less than pending should keep it open without trading"
+        assert_eq!(Ok(()), order.reduce(3));
+        assert_eq!(2, *order.pending_shares());
+        assert!(order.investor.assets().get("HGLG11").is_none());
+    }
+
+    #[test]
+    fn trail_traded_prices() {
+        let asset = Asset::new("HGLG11");
+        let investor = Investor::new("123", "Joe", vec![("HGLG11".into(), 10)]);
+
+        let mut sell_order = Order::<Sell, Open>::new(
+            asset.to_owned(),
+            investor.to_owned(),
+            "1",
+            0.0,
+            5,
+        )
+        .with_instructions(
+            OrderInstructions::new()
+                .with_market_price()
+                .with_trailing_amount(0.5),
+        );
+
+        // "Sell stops should only follow rising prices"
+        for price in [10.0, 11.0, 10.6] {
+            sell_order.track_price(price);
+        }
+
+        assert_eq!(
+            Some(11.0),
+            sell_order.instructions().trailing().unwrap().watermark()
+        );
+        assert_eq!(Some(10.5), sell_order.instructions().stop_price());
+
+        let mut buy_order =
+            Order::<Buy, Open>::new(asset, investor, "2", 9.0, 5)
+                .with_instructions(
+                    OrderInstructions::new().with_trailing_percentage(10.0),
+                );
+
+        // "Buy stops should only follow falling prices"
+        for price in [10.0, 8.0, 8.5] {
+            buy_order.track_price(price);
+        }
+
+        assert_eq!(
+            Some(8.0),
+            buy_order.instructions().trailing().unwrap().watermark()
+        );
+        assert_eq!(Some(8.8), buy_order.instructions().stop_price());
+
+        buy_order.trigger_stop();
+        assert!(!buy_order.instructions().is_stop());
+    }
+
+    #[test]
+    fn replenish_iceberg() {
+        let asset = Asset::new("HGLG11");
+        let investor = Investor::new("123", "Joe", vec![]);
+
+        let mut order = Order::<Buy, Open>::new(asset, investor, "1", 5.0, 25)
+            .with_instructions(
+                OrderInstructions::new().with_display_quantity(10),
+            );
+
+        assert_eq!((10, 15), (*order.pending_shares(), order.reserve_shares()));
+        assert!(!order.replenish());
+
+        // "Filling the peak should keep the order open until replenished"
+        let sequence = order.sequence();
+        assert!(matches!(order.buy(10), Ok(OrderTransition::Open(_))));
+        assert!(order.replenish());
+        assert_eq!((10, 5), (*order.pending_shares(), order.reserve_shares()));
+        assert!(order.sequence() > sequence);
+
+        order.buy(10).unwrap();
+        order.replenish();
+        assert_eq!((5, 0), (*order.pending_shares(), order.reserve_shares()));
+        assert!(matches!(order.buy(5), Ok(OrderTransition::Closed(_))));
+    }
 }
@@ -14,8 +14,11 @@ pub struct Transaction {
     buying_order: OrderTransition<Buy>,
     selling_order: OrderTransition<Sell>,
     traded_shares: u32,
+    price: f32,
     total: f32,
     traded_at: DateTime<Utc>,
+    dark: bool,
+    short_shares: u32,
 }
 
 impl Transaction {
@@ -25,20 +28,51 @@ impl Transaction {
         shares: u32,
         price: f32,
     ) -> Transaction {
+        // Short sales sell the held shares first, so the shares of the trade
+        // still owed by the seller are borrowed
+        let short_shares = if selling_order.instructions().is_short_sell() {
+            shares.min(
+                selling_order
+                    .investor()
+                    .short_position(selling_order.asset_id()),
+            )
+        } else {
+            0
+        };
+
         Transaction {
             id: Uuid::new_v4().to_string(),
             buying_order,
             selling_order,
             traded_shares: shares,
+            price,
             total: shares as f32 * price,
             traded_at: Utc::now(),
+            dark: false,
+            short_shares,
         }
     }
 
+    /// Overrides the trading time, used when replaying past trades.
+    pub fn with_traded_at(mut self, traded_at: DateTime<Utc>) -> Transaction {
+        self.traded_at = traded_at;
+        self
+    }
+
+    /// Marks a trade crossed in the dark book.
+    pub fn with_dark(mut self) -> Transaction {
+        self.dark = true;
+        self
+    }
+
     pub fn id(&self) -> &str {
         self.id.as_ref()
     }
 
+    pub fn asset_id(&self) -> &str {
+        self.buying_order.asset_id()
+    }
+
     pub fn buying_order(&self) -> &OrderTransition<Buy> {
         &self.buying_order
     }
@@ -51,6 +85,10 @@ impl Transaction {
         self.traded_shares
     }
 
+    pub fn price(&self) -> f32 {
+        self.price
+    }
+
     pub fn total(&self) -> f32 {
         self.total
     }
@@ -58,4 +96,17 @@ impl Transaction {
     pub fn traded_at(&self) -> DateTime<Utc> {
         self.traded_at
     }
+
+    /// Shares of the trade sold short, borrowed by the seller.
+    pub fn short_shares(&self) -> u32 {
+        self.short_shares
+    }
+
+    pub fn is_short_sale(&self) -> bool {
+        self.short_shares > 0
+    }
+
+    pub fn is_dark(&self) -> bool {
+        self.dark
+    }
 }
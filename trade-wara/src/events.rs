@@ -0,0 +1,202 @@
+use std::fmt::Debug;
+
+use serde::Serialize;
+
+use crate::{
+    auction::Equilibrium,
+    entities::order::{OrderResolution, Side},
+    session::SessionPhase,
+};
+
+/// Why shares were removed from the book without trading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum CancelReason {
+    Requested,
+    SelfTradePrevention,
+    MarketHalt,
+    /// Shares of a market order left when the opposite side ran out.
+    Unfilled,
+    /// Triggered stop order failing the checks of the book.
+    Rejected,
+    /// Cancelled with every order of an investor or asset.
+    MassCancel,
+    /// Cancelled when the session that sent the order disconnected.
+    Disconnected,
+    /// Cancelled when the kill switch blocked its investor or the market.
+    KillSwitch,
+    /// Cancelled when the margin account of its investor was liquidated.
+    Liquidation,
+    /// Dark order cancelled when an instrument update disabled its dark
+    /// pool.
+    InstrumentUpdated,
+}
+
+/// Lifecycle changes of the orders resting in an `OrderBook`, recorded in
+/// the same sequence they are applied to the book.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event")]
+pub enum OrderEvent {
+    Added {
+        order_id: String,
+        side: Side,
+        price: f32,
+        shares: u32,
+    },
+    Executed {
+        order_id: String,
+        side: Side,
+        price: f32,
+        shares: u32,
+        remaining: u32,
+    },
+    Reduced {
+        order_id: String,
+        side: Side,
+        shares: u32,
+        remaining: u32,
+        reason: CancelReason,
+    },
+    Deleted {
+        order_id: String,
+        side: Side,
+        reason: CancelReason,
+    },
+    /// Order moved to a new price, losing its time priority.
+    Replaced {
+        order_id: String,
+        side: Side,
+        price: f32,
+        shares: u32,
+    },
+}
+
+impl OrderEvent {
+    pub fn order_id(&self) -> &str {
+        match self {
+            OrderEvent::Added { order_id, .. }
+            | OrderEvent::Executed { order_id, .. }
+            | OrderEvent::Reduced { order_id, .. }
+            | OrderEvent::Deleted { order_id, .. }
+            | OrderEvent::Replaced { order_id, .. } => order_id,
+        }
+    }
+
+    pub fn side(&self) -> Side {
+        match self {
+            OrderEvent::Added { side, .. }
+            | OrderEvent::Executed { side, .. }
+            | OrderEvent::Reduced { side, .. }
+            | OrderEvent::Deleted { side, .. }
+            | OrderEvent::Replaced { side, .. } => *side,
+        }
+    }
+}
+
+/// Changes in the trading conditions of an `OrderBook`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event")]
+pub enum MarketEvent {
+    PhaseChanged {
+        asset_id: String,
+        from: SessionPhase,
+        to: SessionPhase,
+    },
+    AuctionStarted {
+        asset_id: String,
+    },
+    IndicativeUncross {
+        asset_id: String,
+        equilibrium: Option<Equilibrium>,
+    },
+    AuctionUncrossed {
+        asset_id: String,
+        equilibrium: Option<Equilibrium>,
+    },
+    TradingHalted {
+        asset_id: String,
+        reason: Option<String>,
+        cancelled_orders: usize,
+        queue_orders: bool,
+    },
+    TradingResumed {
+        asset_id: String,
+        released_orders: usize,
+        rejected_orders: usize,
+    },
+}
+
+impl MarketEvent {
+    pub fn asset_id(&self) -> &str {
+        match self {
+            MarketEvent::PhaseChanged { asset_id, .. }
+            | MarketEvent::AuctionStarted { asset_id }
+            | MarketEvent::IndicativeUncross { asset_id, .. }
+            | MarketEvent::AuctionUncrossed { asset_id, .. }
+            | MarketEvent::TradingHalted { asset_id, .. }
+            | MarketEvent::TradingResumed { asset_id, .. } => asset_id,
+        }
+    }
+}
+
+/// Stage of the order path that turned an order down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum RejectionStage {
+    Throttle,
+    KillSwitch,
+    Risk,
+    Margin,
+    Registry,
+    OrderBook,
+}
+
+/// Market order closing a position of a margin account refused by its
+/// book. The position is retried while the account is being liquidated.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LiquidationFailed {
+    pub investor_id: String,
+    pub asset_id: String,
+    pub side: Side,
+    pub shares: u32,
+    pub reason: String,
+}
+
+impl LiquidationFailed {
+    pub fn new(
+        order: &OrderResolution,
+        reason: impl Debug,
+    ) -> LiquidationFailed {
+        LiquidationFailed {
+            investor_id: order.investor_id().into(),
+            asset_id: order.asset_id().into(),
+            side: order.side(),
+            shares: order.shares(),
+            reason: format!("{:?}", reason),
+        }
+    }
+}
+
+/// Order rejected before or by its book, published back to its investor.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OrderRejected {
+    pub order_id: String,
+    pub investor_id: String,
+    pub asset_id: String,
+    pub stage: RejectionStage,
+    pub reason: String,
+}
+
+impl OrderRejected {
+    pub fn new(
+        order: &OrderResolution,
+        stage: RejectionStage,
+        reason: impl Debug,
+    ) -> OrderRejected {
+        OrderRejected {
+            order_id: order.order_id().into(),
+            investor_id: order.investor_id().into(),
+            asset_id: order.asset_id().into(),
+            stage,
+            reason: format!("{:?}", reason),
+        }
+    }
+}
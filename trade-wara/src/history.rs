@@ -0,0 +1,328 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    sync::Arc,
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::entities::transaction::Transaction;
+
+pub const DEFAULT_HISTORY_CAPACITY: usize = 10_000;
+
+/// Bounded store of the latest transactions indexed by asset, investor and
+/// order id. Once full, the oldest transaction is evicted for every new one
+/// and, when a spill file is set, appended to it as a JSON line.
+#[derive(Debug)]
+pub struct TradeHistory {
+    capacity: usize,
+    first_sequence: u64,
+    transactions: VecDeque<Arc<Transaction>>,
+    by_asset: HashMap<String, VecDeque<u64>>,
+    by_investor: HashMap<String, VecDeque<u64>>,
+    by_order: HashMap<String, VecDeque<u64>>,
+    spill: Option<File>,
+    spilled: u64,
+}
+
+impl Default for TradeHistory {
+    fn default() -> Self {
+        TradeHistory::new(DEFAULT_HISTORY_CAPACITY)
+    }
+}
+
+impl TradeHistory {
+    pub fn new(capacity: usize) -> TradeHistory {
+        TradeHistory {
+            capacity: capacity.max(1),
+            first_sequence: 0,
+            transactions: VecDeque::new(),
+            by_asset: HashMap::new(),
+            by_investor: HashMap::new(),
+            by_order: HashMap::new(),
+            spill: None,
+            spilled: 0,
+        }
+    }
+
+    /// Appends evicted transactions to `path`, creating it when missing.
+    pub fn with_spill(mut self, path: &Path) -> io::Result<TradeHistory> {
+        self.spill =
+            Some(OpenOptions::new().create(true).append(true).open(path)?);
+
+        Ok(self)
+    }
+
+    pub fn record(&mut self, transaction: Arc<Transaction>) {
+        if self.transactions.len() == self.capacity {
+            self.evict();
+        }
+
+        let sequence = self.first_sequence + self.transactions.len() as u64;
+
+        index_sequence(&mut self.by_asset, transaction.asset_id(), sequence);
+
+        for investor_id in [
+            transaction.buying_order().investor_id(),
+            transaction.selling_order().investor_id(),
+        ] {
+            index_sequence(&mut self.by_investor, investor_id, sequence);
+        }
+
+        for order_id in [
+            transaction.buying_order().get_order_id(),
+            transaction.selling_order().get_order_id(),
+        ] {
+            index_sequence(&mut self.by_order, order_id, sequence);
+        }
+
+        self.transactions.push_back(transaction);
+    }
+
+    pub fn by_asset(&self, asset_id: &str) -> Vec<Arc<Transaction>> {
+        self.lookup(&self.by_asset, asset_id)
+    }
+
+    pub fn by_investor(&self, investor_id: &str) -> Vec<Arc<Transaction>> {
+        self.lookup(&self.by_investor, investor_id)
+    }
+
+    pub fn by_order(&self, order_id: &str) -> Vec<Arc<Transaction>> {
+        self.lookup(&self.by_order, order_id)
+    }
+
+    /// Transactions traded in the `[from, to)` range, oldest first.
+    pub fn between(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Vec<Arc<Transaction>> {
+        self.transactions
+            .iter()
+            .filter(|transaction| {
+                transaction.traded_at() >= from && transaction.traded_at() < to
+            })
+            .cloned()
+            .collect()
+    }
+
+    pub fn latest(&self) -> Option<&Arc<Transaction>> {
+        self.transactions.back()
+    }
+
+    pub fn len(&self) -> usize {
+        self.transactions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transactions.is_empty()
+    }
+
+    /// Count of evicted transactions written to the spill file.
+    pub fn spilled(&self) -> u64 {
+        self.spilled
+    }
+
+    fn lookup(
+        &self,
+        index: &HashMap<String, VecDeque<u64>>,
+        key: &str,
+    ) -> Vec<Arc<Transaction>> {
+        let Some(sequences) = index.get(key) else {
+            return vec![];
+        };
+
+        sequences
+            .iter()
+            .map(|sequence| {
+                self.transactions[(sequence - self.first_sequence) as usize]
+                    .clone()
+            })
+            .collect()
+    }
+
+    fn evict(&mut self) {
+        let Some(transaction) = self.transactions.pop_front() else {
+            return;
+        };
+
+        let sequence = self.first_sequence;
+        self.first_sequence += 1;
+
+        unindex_sequence(&mut self.by_asset, transaction.asset_id(), sequence);
+
+        for investor_id in [
+            transaction.buying_order().investor_id(),
+            transaction.selling_order().investor_id(),
+        ] {
+            unindex_sequence(&mut self.by_investor, investor_id, sequence);
+        }
+
+        for order_id in [
+            transaction.buying_order().get_order_id(),
+            transaction.selling_order().get_order_id(),
+        ] {
+            unindex_sequence(&mut self.by_order, order_id, sequence);
+        }
+
+        if let Some(spill) = self.spill.as_mut() {
+            let written = serde_json::to_vec(transaction.as_ref())
+                .map_err(io::Error::from)
+                .and_then(|mut line| {
+                    line.push(b'\n');
+                    spill.write_all(&line)
+                });
+
+            match written {
+                Ok(()) => self.spilled += 1,
+                Err(err) => eprintln!("Failed to spill transaction {:?}", err),
+            }
+        }
+    }
+}
+
+fn index_sequence(
+    index: &mut HashMap<String, VecDeque<u64>>,
+    key: &str,
+    sequence: u64,
+) {
+    let sequences = index.entry(key.into()).or_default();
+
+    // Both sides of a transaction may share the same key
+    if sequences.back() != Some(&sequence) {
+        sequences.push_back(sequence);
+    }
+}
+
+fn unindex_sequence(
+    index: &mut HashMap<String, VecDeque<u64>>,
+    key: &str,
+    sequence: u64,
+) {
+    let Some(sequences) = index.get_mut(key) else {
+        return;
+    };
+
+    if sequences.front() == Some(&sequence) {
+        sequences.pop_front();
+    }
+
+    if sequences.is_empty() {
+        index.remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use crate::entities::{
+        asset::Asset,
+        investor::Investor,
+        order::{Buy, Open, Order, Sell},
+    };
+
+    use super::*;
+
+    fn trade(
+        asset_id: &str,
+        buyer_id: &str,
+        seller_id: &str,
+        at: i64,
+    ) -> Arc<Transaction> {
+        let asset = Asset::new(asset_id);
+
+        Arc::new(
+            Transaction::new(
+                Order::<Buy, Open>::new(
+                    asset.to_owned(),
+                    Investor::new(buyer_id, "Buyer", vec![]),
+                    &format!("B{}", at),
+                    5.0,
+                    10,
+                )
+                .check_order(),
+                Order::<Sell, Open>::new(
+                    asset,
+                    Investor::new(seller_id, "Seller", vec![]),
+                    &format!("S{}", at),
+                    5.0,
+                    10,
+                )
+                .check_order(),
+                10,
+                5.0,
+            )
+            .with_traded_at(Utc.timestamp_opt(at, 0).unwrap()),
+        )
+    }
+
+    #[test]
+    fn query_transactions() {
+        let mut history = TradeHistory::new(10);
+
+        history.record(trade("HGLG11", "1", "2", 10));
+        history.record(trade("MXRF11", "1", "3", 20));
+        history.record(trade("HGLG11", "3", "3", 30));
+
+        assert_eq!(3, history.len());
+        assert_eq!(2, history.by_asset("HGLG11").len());
+        assert_eq!(2, history.by_investor("1").len());
+        assert_eq!(2, history.by_investor("3").len());
+        assert_eq!(
+            "S20",
+            history.by_order("S20")[0].selling_order().get_order_id()
+        );
+        assert!(history.by_order("B99").is_empty());
+
+        let between = history.between(
+            Utc.timestamp_opt(20, 0).unwrap(),
+            Utc.timestamp_opt(30, 0).unwrap(),
+        );
+        assert_eq!(1, between.len());
+        assert_eq!("MXRF11", between[0].asset_id());
+    }
+
+    #[test]
+    fn evict_oldest_transactions() {
+        let mut history = TradeHistory::new(2);
+
+        history.record(trade("HGLG11", "1", "2", 10));
+        history.record(trade("MXRF11", "1", "3", 20));
+        history.record(trade("HGLG11", "3", "3", 30));
+
+        assert_eq!(2, history.len());
+        assert_eq!(1, history.by_asset("HGLG11").len());
+        assert_eq!(1, history.by_investor("1").len());
+        assert!(history.by_investor("2").is_empty());
+        assert!(history.by_order("B10").is_empty());
+        assert_eq!(
+            Utc.timestamp_opt(30, 0).unwrap(),
+            history.latest().unwrap().traded_at()
+        );
+    }
+
+    #[test]
+    fn spill_evicted_transactions() {
+        let path = std::env::temp_dir()
+            .join(format!("trade-history-{}.jsonl", uuid::Uuid::new_v4()));
+
+        let mut history = TradeHistory::new(1).with_spill(&path).unwrap();
+
+        history.record(trade("HGLG11", "1", "2", 10));
+        history.record(trade("HGLG11", "1", "2", 20));
+        history.record(trade("HGLG11", "1", "2", 30));
+
+        assert_eq!(2, history.spilled());
+
+        let spilled = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let lines = spilled.lines().collect::<Vec<_>>();
+        assert_eq!(2, lines.len());
+        assert!(lines[0].contains("\"buying_order_id\":\"B10\""));
+        assert!(lines[1].contains("\"buying_order_id\":\"B20\""));
+    }
+}
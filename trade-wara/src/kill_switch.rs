@@ -0,0 +1,263 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{events::CancelReason, order_book::OrderBook};
+
+/// Who is blocked by the kill switch.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "scope")]
+pub enum KillScope {
+    Investor {
+        investor_id: String,
+    },
+    /// Every investor of every asset.
+    Market,
+}
+
+impl KillScope {
+    /// Id of the blocked investor, or `Market`.
+    pub fn target_id(&self) -> &str {
+        match self {
+            KillScope::Investor { investor_id } => investor_id,
+            KillScope::Market => "Market",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum KillSwitchError {
+    InvestorBlocked(String),
+    MarketBlocked,
+}
+
+/// Status changes of the kill switch, also kept as its audit log.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event")]
+pub enum KillSwitchEvent {
+    Engaged {
+        #[serde(flatten)]
+        scope: KillScope,
+        reason: Option<String>,
+        operator: Option<String>,
+        cancelled_orders: usize,
+        /// RFC 3339 time of the change.
+        at: String,
+    },
+    Released {
+        #[serde(flatten)]
+        scope: KillScope,
+        operator: Option<String>,
+        at: String,
+    },
+}
+
+impl KillSwitchEvent {
+    pub fn scope(&self) -> &KillScope {
+        match self {
+            KillSwitchEvent::Engaged { scope, .. }
+            | KillSwitchEvent::Released { scope, .. } => scope,
+        }
+    }
+}
+
+/// Blocks investors, or the whole market, across every book until they are
+/// enabled again. Unlike halts it does not change the phase of any book.
+#[derive(Debug, Default)]
+pub struct KillSwitch {
+    engaged: HashSet<KillScope>,
+    audit_log: Vec<KillSwitchEvent>,
+}
+
+impl KillSwitch {
+    pub fn new() -> KillSwitch {
+        KillSwitch::default()
+    }
+
+    pub fn is_engaged(&self, scope: &KillScope) -> bool {
+        self.engaged.contains(scope)
+    }
+
+    /// Every engagement and release, oldest first.
+    pub fn audit_log(&self) -> &[KillSwitchEvent] {
+        &self.audit_log
+    }
+
+    /// Blocks the scope and cancels its resting, stop, dark and queued
+    /// orders in every book. Engaging an engaged scope cancels again.
+    pub fn engage(
+        &mut self,
+        scope: KillScope,
+        reason: Option<String>,
+        operator: Option<String>,
+        books: &mut HashMap<String, OrderBook>,
+        now: DateTime<Utc>,
+    ) -> KillSwitchEvent {
+        let cancelled_orders = books
+            .values_mut()
+            .map(|book| {
+                book.cancel_where(
+                    |investor_id, _| match &scope {
+                        KillScope::Investor {
+                            investor_id: blocked_investor_id,
+                        } => investor_id == blocked_investor_id,
+                        KillScope::Market => true,
+                    },
+                    CancelReason::KillSwitch,
+                )
+                .len()
+            })
+            .sum();
+
+        self.engaged.insert(scope.to_owned());
+
+        self.log(KillSwitchEvent::Engaged {
+            scope,
+            reason,
+            operator,
+            cancelled_orders,
+            at: now.to_rfc3339(),
+        })
+    }
+
+    /// Accepts orders of the scope again, returning `None` when it was not
+    /// engaged.
+    pub fn release(
+        &mut self,
+        scope: KillScope,
+        operator: Option<String>,
+        now: DateTime<Utc>,
+    ) -> Option<KillSwitchEvent> {
+        if !self.engaged.remove(&scope) {
+            return None;
+        }
+
+        Some(self.log(KillSwitchEvent::Released {
+            scope,
+            operator,
+            at: now.to_rfc3339(),
+        }))
+    }
+
+    /// Rejects the orders of blocked investors, or every order while the
+    /// market is blocked.
+    pub fn check(&self, investor_id: &str) -> Result<(), KillSwitchError> {
+        if self.is_engaged(&KillScope::Market) {
+            return Err(KillSwitchError::MarketBlocked);
+        }
+
+        if self.is_engaged(&KillScope::Investor {
+            investor_id: investor_id.into(),
+        }) {
+            return Err(KillSwitchError::InvestorBlocked(investor_id.into()));
+        }
+
+        Ok(())
+    }
+
+    fn log(&mut self, event: KillSwitchEvent) -> KillSwitchEvent {
+        self.audit_log.push(event.to_owned());
+        event
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        entities::{
+            asset::Asset,
+            investor::Investor,
+            order::{Buy, Open, Order, OrderItem, Sell},
+        },
+        events::OrderEvent,
+    };
+
+    use super::*;
+
+    #[test]
+    fn block_investor_and_market() {
+        let asset = Asset::new("HGLG11");
+        let foo =
+            Investor::new("123", "Foo", vec![(asset.id().to_owned(), 100)]);
+        let bar = Investor::new("321", "Bar", vec![]);
+
+        let mut book = OrderBook::new(asset.id().into());
+
+        book.submit(
+            Order::<Sell, Open>::new(asset.to_owned(), foo, "A", 6.0, 10)
+                .resolve_type(),
+        )
+        .unwrap();
+        book.submit(
+            Order::<Buy, Open>::new(asset.to_owned(), bar, "B", 5.0, 10)
+                .resolve_type(),
+        )
+        .unwrap();
+        book.drain_events();
+
+        let mut books = HashMap::from([(asset.id().to_owned(), book)]);
+        let mut kill_switch = KillSwitch::new();
+        let foo_scope = KillScope::Investor {
+            investor_id: "123".into(),
+        };
+
+        let event = kill_switch.engage(
+            foo_scope.to_owned(),
+            Some("Compliance review".into()),
+            Some("ops".into()),
+            &mut books,
+            Utc::now(),
+        );
+
+        assert!(matches!(
+            event,
+            KillSwitchEvent::Engaged {
+                cancelled_orders: 1,
+                ..
+            }
+        ));
+        assert_eq!(
+            vec![OrderEvent::Deleted {
+                order_id: "A".into(),
+                side: crate::entities::order::Side::Sell,
+                reason: CancelReason::KillSwitch,
+            }],
+            books.get_mut("HGLG11").unwrap().drain_events()
+        );
+        assert_eq!(
+            Err(KillSwitchError::InvestorBlocked("123".into())),
+            kill_switch.check("123")
+        );
+        assert_eq!(Ok(()), kill_switch.check("321"));
+
+        kill_switch.engage(
+            KillScope::Market,
+            None,
+            None,
+            &mut books,
+            Utc::now(),
+        );
+
+        assert_eq!(0, books["HGLG11"].open_orders("321"));
+        assert_eq!(
+            Err(KillSwitchError::MarketBlocked),
+            kill_switch.check("321")
+        );
+
+        // "Releasing the market should keep the investor blocked"
+        assert!(kill_switch
+            .release(KillScope::Market, None, Utc::now())
+            .is_some());
+        assert!(kill_switch
+            .release(KillScope::Market, None, Utc::now())
+            .is_none());
+        assert_eq!(Ok(()), kill_switch.check("321"));
+        assert!(kill_switch.check("123").is_err());
+
+        kill_switch.release(foo_scope, None, Utc::now());
+
+        assert_eq!(Ok(()), kill_switch.check("123"));
+        assert_eq!(4, kill_switch.audit_log().len());
+    }
+}
@@ -1,10 +1,25 @@
+pub mod admin;
+pub mod allocation;
+pub mod auction;
+pub mod circuit_breaker;
+pub mod dark_book;
 pub mod dto;
 pub mod entities;
+pub mod events;
+pub mod history;
+pub mod kill_switch;
+pub mod margin;
+pub mod market_data;
+pub mod mass_cancel;
 pub mod order_book;
+pub mod registry;
+pub mod risk;
+pub mod session;
+pub mod throttle;
 
 use std::{cmp::Ordering, ops::Deref};
 
-#[derive(Debug, PartialEq, PartialOrd, Clone)]
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
 pub struct ComparableFloat(pub f32);
 
 impl Eq for ComparableFloat {}
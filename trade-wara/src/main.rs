@@ -1,13 +1,16 @@
 use std::{
     collections::HashMap,
+    env,
+    path::Path,
     sync::{
-        mpsc::{channel, TryRecvError},
+        mpsc::{channel, Sender, TryRecvError},
         Arc, Mutex,
     },
     thread,
     time::Duration,
 };
 
+use chrono::{DateTime, FixedOffset, NaiveTime, Utc};
 use rdkafka::{
     config::RDKafkaLogLevel,
     consumer::{BaseConsumer, CommitMode, Consumer},
@@ -15,21 +18,486 @@ use rdkafka::{
     ClientConfig, Message,
 };
 use trade_wara::{
+    admin::AdminCommand,
     entities::{
         order::{OrderItem, OrderResolution},
         transaction::Transaction,
     },
+    events::{CancelReason, LiquidationFailed, OrderRejected, RejectionStage},
+    kill_switch::KillSwitch,
+    margin::{MarginAccounts, MarginStatus},
+    market_data::{
+        candles::{CandleAggregator, Interval},
+        level3::L3Feed,
+    },
+    mass_cancel::{CancelledOrder, InstrumentCancelSummary, MassCancel},
     order_book::OrderBook,
+    registry::AssetRegistry,
+    risk::PreTradeRisk,
+    session::{
+        Clock, SessionCalendar, SessionPhase, SystemClock, TradingSession,
+    },
+    throttle::{RateLimit, Throttle},
 };
 
+const ORDERS_TOPIC: &str = "orders_topic";
+const ADMIN_TOPIC: &str = "admin_topic";
+const TRANSACTIONS_TOPIC: &str = "transactions_topic";
+const ORDER_FEED_TOPIC: &str = "order_feed_topic";
+const TICKER_TOPIC: &str = "ticker_topic";
+const CANDLES_TOPIC: &str = "candles_topic";
+const MARKET_EVENTS_TOPIC: &str = "market_events_topic";
+const MASS_CANCELS_TOPIC: &str = "mass_cancels_topic";
+const KILL_SWITCH_TOPIC: &str = "kill_switch_topic";
+const THROTTLE_METRICS_TOPIC: &str = "throttle_metrics_topic";
+const MARGIN_EVENTS_TOPIC: &str = "margin_events_topic";
+const ORDER_REJECTIONS_TOPIC: &str = "order_rejections_topic";
+const LIQUIDATION_FAILURES_TOPIC: &str = "liquidation_failures_topic";
+
+const DEFAULT_ASSET_REGISTRY_PATH: &str = "assets.json";
+const DEFAULT_RISK_LIMITS_PATH: &str = "risk_limits.json";
+
+/// Orders per second accepted from each investor, and at once.
+const ORDER_RATE: f64 = 50.0;
+const ORDER_BURST: u32 = 200;
+
+/// Seconds between the attempts to close the positions of an account being
+/// liquidated.
+const LIQUIDATION_RETRY_SECS: i64 = 1;
+
+/// Message waiting to be sent by the publisher.
+struct Publication {
+    topic: &'static str,
+    key: String,
+    payload: Vec<u8>,
+}
+
+impl Publication {
+    fn new<T: serde::Serialize>(
+        topic: &'static str,
+        key: &str,
+        message: &T,
+    ) -> Publication {
+        let Ok(payload) = serde_json::to_vec(message) else {
+            panic!("Error on serializing {} message", topic);
+        };
+
+        Publication {
+            topic,
+            key: key.into(),
+            payload,
+        }
+    }
+}
+
+/// Publishes everything derived from the order books.
+struct MarketData {
+    publications: Sender<Publication>,
+    order_feed: L3Feed,
+    candles: CandleAggregator,
+}
+
+impl MarketData {
+    fn new(publications: Sender<Publication>) -> MarketData {
+        MarketData {
+            publications,
+            order_feed: L3Feed::new(),
+            candles: CandleAggregator::new(
+                Interval::ALL.to_vec(),
+                chrono::Duration::seconds(1),
+            ),
+        }
+    }
+
+    fn publish_transaction(&mut self, transaction: &Transaction) {
+        self.send(TRANSACTIONS_TOPIC, transaction.id(), transaction);
+
+        for candle in self.candles.record(transaction) {
+            self.send(CANDLES_TOPIC, candle.asset_id(), &candle);
+        }
+    }
+
+    fn publish_candles(&mut self, now: DateTime<Utc>) {
+        for candle in self.candles.advance(now) {
+            self.send(CANDLES_TOPIC, candle.asset_id(), &candle);
+        }
+    }
+
+    /// Publishes the order feed, market events and ticker of the book.
+    fn publish_book(&mut self, book: &mut OrderBook) {
+        for event in book.drain_events() {
+            if let Some(message) =
+                self.order_feed.process(book.asset_id(), &event)
+            {
+                self.send(ORDER_FEED_TOPIC, book.asset_id(), &message);
+            }
+        }
+
+        for event in book.drain_market_events() {
+            self.send(MARKET_EVENTS_TOPIC, event.asset_id(), &event);
+        }
+
+        if let Some(ticker) = book.take_ticker_update() {
+            self.send(TICKER_TOPIC, ticker.asset_id(), &ticker);
+        }
+    }
+
+    fn publish_rejection(&self, rejection: &OrderRejected) {
+        println!("Order Rejected {:?}\n\n", rejection);
+
+        self.send(ORDER_REJECTIONS_TOPIC, &rejection.investor_id, rejection);
+    }
+
+    fn send<T: serde::Serialize>(
+        &self,
+        topic: &'static str,
+        key: &str,
+        message: &T,
+    ) {
+        self.publications
+            .send(Publication::new(topic, key, message))
+            .unwrap();
+    }
+}
+
+/// Checks applied to every order before it reaches its book.
+struct PreTradeControls {
+    throttle: Throttle,
+    kill_switch: KillSwitch,
+    risk: PreTradeRisk,
+    margin: MarginAccounts,
+    /// Margin accounts being liquidated, with the time of their next
+    /// attempt.
+    liquidations: HashMap<String, DateTime<Utc>>,
+}
+
+impl PreTradeControls {
+    /// Rejects the order if its investor is blocked, it fails a risk check
+    /// or its margin account cannot cover it.
+    fn check(
+        &self,
+        order: &OrderResolution,
+        book_hash: &HashMap<String, OrderBook>,
+        registry: &AssetRegistry,
+        now: DateTime<Utc>,
+    ) -> Result<(), OrderRejected> {
+        self.kill_switch.check(order.investor_id()).map_err(|err| {
+            OrderRejected::new(order, RejectionStage::KillSwitch, err)
+        })?;
+
+        self.risk
+            .check(order, book_hash, now)
+            .map_err(|rejection| {
+                OrderRejected::new(order, RejectionStage::Risk, rejection)
+            })?;
+
+        self.margin
+            .check(order, book_hash, registry)
+            .map_err(|err| {
+                OrderRejected::new(order, RejectionStage::Margin, err)
+            })
+    }
+
+    /// Updates the positions and margin accounts with a trade, publishing
+    /// the margin calls and liquidations it causes. Liquidations are queued
+    /// until `liquidate`, since the books are borrowed while trading.
+    fn record(
+        &mut self,
+        transaction: &Transaction,
+        registry: &AssetRegistry,
+        market_data: &MarketData,
+    ) {
+        self.risk.record(transaction);
+
+        for snapshot in self.margin.record(transaction, registry) {
+            println!("Margin {:?}\n\n", snapshot);
+
+            if snapshot.status == MarginStatus::Liquidation {
+                self.liquidations.insert(
+                    snapshot.investor_id.to_owned(),
+                    DateTime::<Utc>::MIN_UTC,
+                );
+            }
+
+            market_data.send(
+                MARGIN_EVENTS_TOPIC,
+                &snapshot.investor_id,
+                &snapshot,
+            );
+        }
+    }
+
+    /// Cancels every order of the queued margin accounts and sends market
+    /// orders closing their positions, skipping the pre-trade checks. Books
+    /// not matching continuously are skipped, and accounts keep being
+    /// retried until their positions are closed.
+    fn liquidate(
+        &mut self,
+        book_hash: &mut HashMap<String, OrderBook>,
+        registry: &AssetRegistry,
+        market_data: &mut MarketData,
+        now: DateTime<Utc>,
+    ) {
+        let mut investor_ids = self
+            .liquidations
+            .iter()
+            .filter(|(_, retry_at)| **retry_at <= now)
+            .map(|(investor_id, _)| investor_id.to_owned())
+            .collect::<Vec<_>>();
+
+        investor_ids.sort();
+
+        for investor_id in investor_ids {
+            for book in book_hash.values_mut() {
+                book.cancel_where(
+                    |order_investor_id, _| order_investor_id == investor_id,
+                    CancelReason::Liquidation,
+                );
+                market_data.publish_book(book);
+            }
+
+            for order in self.margin.liquidation_orders(&investor_id, registry)
+            {
+                let Some(book) = book_hash
+                    .get_mut(order.asset_id())
+                    .filter(|book| book.accepts_market_orders())
+                else {
+                    continue;
+                };
+
+                let failed = LiquidationFailed::new(&order, "");
+
+                match book.submit(order) {
+                    Err(err) => market_data.send(
+                        LIQUIDATION_FAILURES_TOPIC,
+                        &investor_id,
+                        &LiquidationFailed {
+                            reason: format!("{:?}", err),
+                            ..failed
+                        },
+                    ),
+                    Ok(transactions) => {
+                        transactions.iter().for_each(|transaction| {
+                            self.record(transaction, registry, market_data);
+                            market_data.publish_transaction(transaction)
+                        })
+                    }
+                }
+
+                market_data.publish_book(book);
+            }
+
+            // Unfilled shares are retried, as the book may get new orders
+            match self.margin.is_liquidating(&investor_id, registry) {
+                true => self.liquidations.insert(
+                    investor_id,
+                    now + chrono::Duration::seconds(LIQUIDATION_RETRY_SECS),
+                ),
+                false => self.liquidations.remove(&investor_id),
+            };
+        }
+    }
+}
+
+/// B3 equities trading hours, in Brasília time.
+fn b3_calendar() -> SessionCalendar {
+    let at = |hour, minute| NaiveTime::from_hms_opt(hour, minute, 0).unwrap();
+
+    SessionCalendar::new(FixedOffset::west_opt(3 * 3600).unwrap())
+        .with_phase(at(9, 30), SessionPhase::PreOpen)
+        .with_phase(at(9, 45), SessionPhase::OpeningAuction)
+        .with_phase(at(10, 0), SessionPhase::Continuous)
+        .with_phase(at(16, 55), SessionPhase::ClosingAuction)
+        .with_phase(at(17, 0), SessionPhase::Closed)
+}
+
+/// Applies an admin command to the registry and the books it affects.
+fn apply_admin_command(
+    command: AdminCommand,
+    registry: &mut AssetRegistry,
+    book_hash: &mut HashMap<String, OrderBook>,
+    new_session: &dyn Fn() -> TradingSession,
+    market_data: &mut MarketData,
+    controls: &mut PreTradeControls,
+    now: DateTime<Utc>,
+) {
+    match command {
+        AdminCommand::UpsertInstrument(instrument) => {
+            let asset = instrument.asset().to_owned();
+
+            registry.upsert(instrument);
+
+            // Books of inactive instruments are left as they are, since new
+            // orders to them are rejected
+            for asset in [asset.fractional(), asset.to_owned()] {
+                let (Some(book), Ok(instrument)) = (
+                    book_hash.get_mut(asset.id()),
+                    registry.resolve(asset.id()),
+                ) else {
+                    continue;
+                };
+
+                let cancelled_orders = instrument
+                    .update_book(book)
+                    .into_iter()
+                    .map(|(order_id, side)| CancelledOrder {
+                        asset_id: asset.id().to_owned(),
+                        order_id,
+                        side,
+                    })
+                    .collect::<Vec<_>>();
+
+                if !cancelled_orders.is_empty() {
+                    market_data.send(
+                        MASS_CANCELS_TOPIC,
+                        asset.id(),
+                        &InstrumentCancelSummary {
+                            asset_id: asset.id().to_owned(),
+                            reason: CancelReason::InstrumentUpdated,
+                            cancelled_orders,
+                        },
+                    );
+                }
+            }
+
+            println!("Instrument {} updated", asset.id());
+        }
+        AdminCommand::Halt { asset_id, halt } => {
+            // Assets may be halted before their first order is received
+            let book = match book_hash.get_mut(&asset_id) {
+                Some(book) => book,
+                None => match registry.resolve(&asset_id) {
+                    Ok(instrument) => book_hash
+                        .entry(asset_id)
+                        .or_insert(instrument.create_book(new_session())),
+                    Err(err) => {
+                        println!("Halt Rejected {:?}\n\n", err);
+                        return;
+                    }
+                },
+            };
+
+            match book.halt(halt) {
+                Err(err) => println!("Halt Failed {:?}\n\n", err),
+                Ok(transactions) => {
+                    transactions.iter().for_each(|transaction| {
+                        controls.record(transaction, registry, market_data);
+                        market_data.publish_transaction(transaction)
+                    })
+                }
+            }
+
+            market_data.publish_book(book);
+        }
+        AdminCommand::Resume { asset_id } => {
+            let Some(book) = book_hash.get_mut(&asset_id) else {
+                println!("Resume Rejected {} is not halted\n\n", asset_id);
+                return;
+            };
+
+            match book.resume() {
+                Err(err) => println!("Resume Failed {:?}\n\n", err),
+                Ok(transactions) => {
+                    transactions.iter().for_each(|transaction| {
+                        controls.record(transaction, registry, market_data);
+                        market_data.publish_transaction(transaction)
+                    })
+                }
+            }
+
+            market_data.publish_book(book);
+        }
+        AdminCommand::Kill {
+            scope,
+            reason,
+            operator,
+        } => {
+            let event = controls
+                .kill_switch
+                .engage(scope, reason, operator, book_hash, now);
+
+            println!("Kill Switch {:?}\n\n", event);
+
+            market_data.send(
+                KILL_SWITCH_TOPIC,
+                event.scope().target_id(),
+                &event,
+            );
+
+            // The books publish the cancelled orders
+            for book in book_hash.values_mut() {
+                market_data.publish_book(book);
+            }
+        }
+        AdminCommand::Enable { scope, operator } => {
+            let Some(event) =
+                controls
+                    .kill_switch
+                    .release(scope.to_owned(), operator, now)
+            else {
+                println!("Enable Rejected {:?} is not blocked\n\n", scope);
+                return;
+            };
+
+            println!("Kill Switch {:?}\n\n", event);
+
+            market_data.send(
+                KILL_SWITCH_TOPIC,
+                event.scope().target_id(),
+                &event,
+            );
+        }
+        AdminCommand::Locate {
+            investor_id,
+            asset_id,
+            shares,
+        } => {
+            controls.risk.locate(&investor_id, &asset_id, shares);
+
+            println!(
+                "Located {} {} shares for {}, {} available",
+                shares,
+                asset_id,
+                investor_id,
+                controls.risk.located(&investor_id, &asset_id)
+            );
+        }
+        AdminCommand::MarginDeposit { investor_id, cash } => {
+            controls.margin.deposit(&investor_id, cash);
+
+            println!(
+                "Margin {:?}\n\n",
+                controls.margin.margin(&investor_id, registry)
+            );
+        }
+    }
+}
+
 fn main() {
-    const ORDERS_TOPIC: &str = "orders_topic";
-    const TRANSACTIONS_TOPIC: &str = "transactions_topic";
+    let registry_path = env::var("ASSET_REGISTRY_PATH")
+        .unwrap_or(DEFAULT_ASSET_REGISTRY_PATH.into());
+
+    let mut registry = AssetRegistry::load(Path::new(&registry_path))
+        .expect("Failed to load asset registry");
+
+    let risk_limits_path =
+        env::var("RISK_LIMITS_PATH").unwrap_or(DEFAULT_RISK_LIMITS_PATH.into());
 
-    let book_hash = Arc::new(Mutex::new(HashMap::new()));
+    let mut controls = PreTradeControls {
+        throttle: Throttle::new(RateLimit::new(ORDER_RATE, ORDER_BURST)),
+        kill_switch: KillSwitch::new(),
+        risk: PreTradeRisk::load(Path::new(&risk_limits_path))
+            .expect("Failed to load risk limits"),
+        margin: MarginAccounts::new(),
+        liquidations: HashMap::new(),
+    };
+
+    let book_hash = Arc::new(Mutex::new(HashMap::<String, OrderBook>::new()));
 
     let orders = channel::<Arc<dyn OrderItem>>();
-    let transactions = channel::<Arc<Transaction>>();
+    let admin_commands = channel::<AdminCommand>();
+    let mass_cancels = channel::<MassCancel>();
+    let publications = channel::<Publication>();
 
     println!("TradeWara service started");
 
@@ -47,7 +515,7 @@ fn main() {
                 .expect("Failed to create consumer");
 
             consumer
-                .subscribe(&[ORDERS_TOPIC])
+                .subscribe(&[ORDERS_TOPIC, ADMIN_TOPIC])
                 .expect("Failed to subscribe");
 
             // List Kafka here
@@ -65,13 +533,37 @@ fn main() {
                             .payload()
                             .expect("Failed to get message payload");
 
-                        let order: OrderResolution =
-                            serde_json::from_slice(payload)
-                                .expect("Failed to parse message payload");
+                        let sent = if msg.topic() == ADMIN_TOPIC {
+                            match serde_json::from_slice::<AdminCommand>(
+                                payload,
+                            ) {
+                                Ok(command) => {
+                                    admin_commands.0.send(command).is_ok()
+                                }
+                                // Malformed commands are skipped, not retried
+                                Err(err) => {
+                                    println!(
+                                        "Admin Command Rejected {:?}\n\n",
+                                        err
+                                    );
+                                    true
+                                }
+                            }
+                        } else if let Ok(request) =
+                            serde_json::from_slice::<MassCancel>(payload)
+                        {
+                            mass_cancels.0.send(request).is_ok()
+                        } else {
+                            let order: OrderResolution =
+                                serde_json::from_slice(payload)
+                                    .expect("Failed to parse message payload");
+
+                            let order: Box<dyn OrderItem> = order.into();
 
-                        let order: Box<dyn OrderItem> = order.into();
+                            orders.0.send(order.into()).is_ok()
+                        };
 
-                        if let Ok(()) = orders.0.send(order.into()) {
+                        if sent {
                             consumer
                                 .commit_message(&msg, CommitMode::Sync)
                                 .unwrap();
@@ -85,27 +577,153 @@ fn main() {
 
     thread::Builder::new()
         .name("trade-matcher".into())
-        .spawn(move || loop {
-            if let Ok(order) = orders.1.try_recv() {
+        .spawn(move || {
+            let mut market_data = MarketData::new(publications.0);
+            let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+            let calendar = b3_calendar();
+            let new_session =
+                || TradingSession::new(calendar.clone(), clock.clone());
+
+            loop {
+                market_data.publish_candles(clock.now());
+
                 let mut book_hash = book_hash.lock().unwrap();
 
-                let book = book_hash
-                    .entry(order.asset_id().to_owned())
-                    .or_insert(OrderBook::new(order.asset_id().to_string()));
+                if let Ok(command) = admin_commands.1.try_recv() {
+                    apply_admin_command(
+                        command,
+                        &mut registry,
+                        &mut book_hash,
+                        &new_session,
+                        &mut market_data,
+                        &mut controls,
+                        clock.now(),
+                    );
+                }
+
+                // Books publish their cancel events right below
+                if let Ok(request) = mass_cancels.1.try_recv() {
+                    let summary = request.apply(&mut book_hash);
 
-                let order = order.resolve_type();
+                    market_data.send(
+                        MASS_CANCELS_TOPIC,
+                        request.target_id(),
+                        &summary,
+                    );
+                }
 
-                //println!("Received order: {:#?}", order);
+                for book in book_hash.values_mut() {
+                    match book.update_session() {
+                        Err(err) => println!("Session Failed {:?}\n\n", err),
+                        Ok(transactions) => {
+                            transactions.iter().for_each(|transaction| {
+                                controls.record(
+                                    transaction,
+                                    &registry,
+                                    &market_data,
+                                );
+                                market_data.publish_transaction(transaction)
+                            })
+                        }
+                    }
 
-                if let Err(err) = book.append(order) {
-                    panic!("{:#?}", err);
+                    market_data.publish_book(book);
                 }
 
-                match book.try_match() {
-                    Err(err) => println!("Match Failed {:?}\n\n", err),
-                    Ok(transaction) => {
-                        transactions.0.send(transaction).unwrap();
+                // Including the accounts liquidated by the last order
+                controls.liquidate(
+                    &mut book_hash,
+                    &registry,
+                    &mut market_data,
+                    clock.now(),
+                );
+
+                if let Ok(order) = orders.1.try_recv() {
+                    let order = order.resolve_type();
+
+                    //println!("Received order: {:#?}", order);
+
+                    // Every order counts towards the rate of its investor
+                    let investor_id = order.investor_id();
+
+                    if let Err(err) =
+                        controls.throttle.check(investor_id, clock.now())
+                    {
+                        market_data.publish_rejection(&OrderRejected::new(
+                            &order,
+                            RejectionStage::Throttle,
+                            err,
+                        ));
+
+                        if let Some(metrics) =
+                            controls.throttle.metrics(investor_id)
+                        {
+                            market_data.send(
+                                THROTTLE_METRICS_TOPIC,
+                                investor_id,
+                                metrics,
+                            );
+                        }
+
+                        continue;
+                    }
+
+                    if let Err(rejection) = controls.check(
+                        &order,
+                        &book_hash,
+                        &registry,
+                        clock.now(),
+                    ) {
+                        market_data.publish_rejection(&rejection);
+                        continue;
                     }
+
+                    let instrument = match registry.resolve(order.asset_id()) {
+                        Ok(instrument) => instrument,
+                        Err(err) => {
+                            market_data.publish_rejection(&OrderRejected::new(
+                                &order,
+                                RejectionStage::Registry,
+                                err,
+                            ));
+                            continue;
+                        }
+                    };
+
+                    let book = book_hash
+                        .entry(order.asset_id().to_owned())
+                        .or_insert_with(|| {
+                            instrument.create_book(new_session())
+                        });
+
+                    // The book takes the order, so its rejection is built
+                    // beforehand
+                    let rejected = OrderRejected::new(
+                        &order,
+                        RejectionStage::OrderBook,
+                        "",
+                    );
+
+                    match book.submit(order) {
+                        Err(err) => {
+                            market_data.publish_rejection(&OrderRejected {
+                                reason: format!("{:?}", err),
+                                ..rejected
+                            })
+                        }
+                        Ok(transactions) => {
+                            transactions.iter().for_each(|transaction| {
+                                controls.record(
+                                    transaction,
+                                    &registry,
+                                    &market_data,
+                                );
+                                market_data.publish_transaction(transaction)
+                            })
+                        }
+                    }
+
+                    market_data.publish_book(book);
                 }
             }
         })
@@ -121,22 +739,16 @@ fn main() {
         .expect("Failed to create publisher");
 
     loop {
-        match transactions.1.try_recv() {
-            Ok(transaction) => {
-                //let transaction = serde_json::to_string(transaction.as_ref());
-
-                // Publish transaction to Kafka here
-                let Ok(payload) = serde_json::to_vec(transaction.as_ref()) else {
-                    panic!("Error on serializing transaction");
-                };
-
+        match publications.1.try_recv() {
+            Ok(publication) => {
+                // Publish transactions and market data to Kafka here
                 publisher
                     .send(
-                        BaseRecord::to(TRANSACTIONS_TOPIC)
-                            .key(transaction.id())
-                            .payload(&payload),
+                        BaseRecord::to(publication.topic)
+                            .key(&publication.key)
+                            .payload(&publication.payload),
                     )
-                    .expect("Failed to send transaction");
+                    .expect("Failed to send publication");
             }
             Err(TryRecvError::Empty) => continue,
             Err(TryRecvError::Disconnected) => {
@@ -0,0 +1,700 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    entities::{
+        asset::Asset,
+        investor::Investor,
+        order::{
+            Buy, Open, Order, OrderInstructions, OrderItem, OrderResolution,
+            Sell, Side,
+        },
+        transaction::Transaction,
+    },
+    order_book::OrderBook,
+    registry::AssetRegistry,
+};
+
+/// Equity required to hold a position of an asset, as fractions of its
+/// market value: `initial` to open it and `maintenance` to keep it.
+/// Assets without requirements are bought with cash only.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MarginRequirement {
+    initial: f32,
+    maintenance: f32,
+}
+
+impl Default for MarginRequirement {
+    fn default() -> Self {
+        MarginRequirement {
+            initial: 1.0,
+            maintenance: 1.0,
+        }
+    }
+}
+
+impl MarginRequirement {
+    pub fn new(initial: f32, maintenance: f32) -> MarginRequirement {
+        MarginRequirement {
+            initial,
+            maintenance: maintenance.min(initial),
+        }
+    }
+
+    pub fn initial(&self) -> f32 {
+        self.initial
+    }
+
+    pub fn maintenance(&self) -> f32 {
+        self.maintenance
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MarginError {
+    InsufficientMargin {
+        equity: f32,
+        required: f32,
+    },
+    /// Market and pegged orders of assets that never traded.
+    NoMarkPrice(String),
+    /// Orders increasing a position of an account being liquidated.
+    Liquidating,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum MarginStatus {
+    #[default]
+    Healthy,
+    /// Equity below the maintenance requirement.
+    MarginCall,
+    /// Equity below the liquidation level of the maintenance requirement.
+    Liquidation,
+}
+
+/// Margin of an account, its positions marked at the last traded prices.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MarginSnapshot {
+    pub investor_id: String,
+    pub status: MarginStatus,
+    pub equity: f32,
+    pub initial_requirement: f32,
+    pub maintenance_requirement: f32,
+}
+
+#[derive(Debug, Default, Clone)]
+struct MarginAccount {
+    cash: f32,
+    positions: HashMap<String, i64>,
+    status: MarginStatus,
+}
+
+/// Accounts of the investors trading on margin. Investors without an
+/// account are not checked.
+#[derive(Debug)]
+pub struct MarginAccounts {
+    accounts: HashMap<String, MarginAccount>,
+    /// Accounts with a position in each asset.
+    holders: HashMap<String, HashSet<String>>,
+    last_prices: HashMap<String, f32>,
+    liquidation_level: f32,
+}
+
+impl Default for MarginAccounts {
+    fn default() -> Self {
+        MarginAccounts {
+            accounts: HashMap::new(),
+            holders: HashMap::new(),
+            last_prices: HashMap::new(),
+            liquidation_level: 0.5,
+        }
+    }
+}
+
+impl MarginAccounts {
+    pub fn new() -> MarginAccounts {
+        MarginAccounts::default()
+    }
+
+    /// Fraction of the maintenance requirement below which accounts are
+    /// liquidated, half of it by default.
+    pub fn with_liquidation_level(mut self, level: f32) -> MarginAccounts {
+        self.liquidation_level = level;
+        self
+    }
+
+    /// Puts an investor in margin mode with `cash`, or deposits it into its
+    /// open account.
+    pub fn deposit(&mut self, investor_id: &str, cash: f32) {
+        self.accounts.entry(investor_id.into()).or_default().cash += cash;
+    }
+
+    pub fn is_margin_account(&self, investor_id: &str) -> bool {
+        self.accounts.contains_key(investor_id)
+    }
+
+    pub fn margin(
+        &self,
+        investor_id: &str,
+        registry: &AssetRegistry,
+    ) -> Option<MarginSnapshot> {
+        let account = self.accounts.get(investor_id)?;
+        let (equity, initial_requirement, maintenance_requirement) = evaluate(
+            account.cash,
+            &account.positions,
+            &self.last_prices,
+            registry,
+        );
+
+        Some(MarginSnapshot {
+            investor_id: investor_id.into(),
+            status: self.status(equity, maintenance_requirement),
+            equity,
+            initial_requirement,
+            maintenance_requirement,
+        })
+    }
+
+    /// Rejects orders of margin accounts whose equity would not cover the
+    /// initial requirement of their positions once filled, along with the
+    /// open orders of the account on the side of each asset requiring the
+    /// most margin. Orders reducing the position are always accepted, and
+    /// the only ones accepted from accounts being liquidated.
+    pub fn check(
+        &self,
+        order: &OrderResolution,
+        books: &HashMap<String, OrderBook>,
+        registry: &AssetRegistry,
+    ) -> Result<(), MarginError> {
+        let Some(account) = self.accounts.get(order.investor_id()) else {
+            return Ok(());
+        };
+
+        let asset_id = order.asset_id();
+        let instructions = order.instructions();
+
+        let price = if instructions.is_market() || instructions.peg().is_some()
+        {
+            *self
+                .last_prices
+                .get(asset_id)
+                .ok_or(MarginError::NoMarkPrice(asset_id.into()))?
+        } else {
+            order.price()
+        };
+
+        let shares = match order.side() {
+            Side::Buy => order.shares() as i64,
+            Side::Sell => -(order.shares() as i64),
+        };
+
+        let current = account.positions.get(asset_id).copied().unwrap_or(0);
+
+        if (current + shares).abs() <= current.abs() {
+            return Ok(());
+        }
+
+        if account.status == MarginStatus::Liquidation {
+            return Err(MarginError::Liquidating);
+        }
+
+        let mut positions = account.positions.to_owned();
+        *positions.entry(asset_id.into()).or_insert(0) += shares;
+
+        let mut cash = account.cash - shares as f32 * price;
+
+        let mut last_prices = self.last_prices.to_owned();
+        last_prices.insert(asset_id.into(), price);
+
+        // Open orders are filled at the marking price
+        for (book_asset_id, book) in books {
+            let open = book.open_shares(order.investor_id());
+
+            if open.buy == 0 && open.sell == 0 {
+                continue;
+            }
+
+            let mark = last_prices.get(book_asset_id).copied().unwrap_or(0.0);
+            let position = positions.entry(book_asset_id.into()).or_insert(0);
+            let filled =
+                [*position + open.buy as i64, *position - open.sell as i64]
+                    .into_iter()
+                    .max_by_key(|shares| shares.abs())
+                    .unwrap_or(*position);
+
+            cash -= (filled - *position) as f32 * mark;
+            *position = filled;
+        }
+
+        let (equity, required, _) =
+            evaluate(cash, &positions, &last_prices, registry);
+
+        if equity < required {
+            return Err(MarginError::InsufficientMargin { equity, required });
+        }
+
+        Ok(())
+    }
+
+    /// Updates the accounts of both investors of a trade and marks the
+    /// accounts holding its asset to its price, returning the accounts whose
+    /// status changed: margin calls, liquidations and accounts back above
+    /// maintenance.
+    pub fn record(
+        &mut self,
+        transaction: &Transaction,
+        registry: &AssetRegistry,
+    ) -> Vec<MarginSnapshot> {
+        let asset_id = transaction.asset_id();
+        let shares = transaction.traded_shares() as i64;
+        let buyer_id = transaction.buying_order().investor_id();
+        let seller_id = transaction.selling_order().investor_id();
+
+        self.last_prices
+            .insert(asset_id.into(), transaction.price());
+
+        self.trade(buyer_id, asset_id, shares, -transaction.total());
+        self.trade(seller_id, asset_id, -shares, transaction.total());
+
+        // Parties closing their position are no longer holders
+        let mut investor_ids = self
+            .holders
+            .get(asset_id)
+            .map(|holders| holders.iter().cloned().collect::<HashSet<_>>())
+            .unwrap_or_default();
+
+        investor_ids.extend([buyer_id.to_owned(), seller_id.to_owned()]);
+
+        let mut changed = investor_ids
+            .iter()
+            .filter_map(|investor_id| {
+                let account = self.accounts.get(investor_id)?;
+                let snapshot = self.margin(investor_id, registry)?;
+
+                (snapshot.status != account.status).then_some(snapshot)
+            })
+            .collect::<Vec<_>>();
+
+        changed.sort_by(|a, b| a.investor_id.cmp(&b.investor_id));
+
+        for snapshot in changed.iter() {
+            if let Some(account) = self.accounts.get_mut(&snapshot.investor_id)
+            {
+                account.status = snapshot.status;
+            }
+        }
+
+        changed
+    }
+
+    /// Whether the account is being liquidated and still has positions
+    /// market orders can close.
+    pub fn is_liquidating(
+        &self,
+        investor_id: &str,
+        registry: &AssetRegistry,
+    ) -> bool {
+        self.accounts.get(investor_id).is_some_and(|account| {
+            account.status == MarginStatus::Liquidation
+                && !self.closable_positions(investor_id, registry).is_empty()
+        })
+    }
+
+    /// Market orders closing every position of an account, sent when it is
+    /// liquidated. Odd lots left by a change of the round lot of an asset
+    /// are kept.
+    pub fn liquidation_orders(
+        &self,
+        investor_id: &str,
+        registry: &AssetRegistry,
+    ) -> Vec<OrderResolution> {
+        self.closable_positions(investor_id, registry)
+            .into_iter()
+            .map(|(asset_id, shares)| {
+                let asset = Asset::new(&asset_id);
+                let order_id = Uuid::new_v4().to_string();
+                let instructions = OrderInstructions::new().with_market_price();
+                let count = shares.unsigned_abs() as u32;
+
+                match shares > 0 {
+                    true => Order::<Sell, Open>::new(
+                        asset,
+                        Investor::new(
+                            investor_id,
+                            investor_id,
+                            vec![(asset_id.to_owned(), count)],
+                        ),
+                        &order_id,
+                        0.0,
+                        count,
+                    )
+                    .with_instructions(instructions)
+                    .resolve_type(),
+                    false => Order::<Buy, Open>::new(
+                        asset,
+                        Investor::new(investor_id, investor_id, vec![]),
+                        &order_id,
+                        0.0,
+                        count,
+                    )
+                    .with_instructions(instructions)
+                    .resolve_type(),
+                }
+            })
+            .collect()
+    }
+
+    /// Positions of an account rounded down to the round lot of their
+    /// asset, sorted by asset.
+    fn closable_positions(
+        &self,
+        investor_id: &str,
+        registry: &AssetRegistry,
+    ) -> Vec<(String, i64)> {
+        let Some(account) = self.accounts.get(investor_id) else {
+            return vec![];
+        };
+
+        let mut positions = account
+            .positions
+            .iter()
+            .map(|(asset_id, shares)| {
+                let round_lot =
+                    registry.resolve(asset_id).map_or(1, |instrument| {
+                        instrument.reference_data().round_lot()
+                    }) as i64;
+
+                (asset_id.to_owned(), shares - shares % round_lot)
+            })
+            .filter(|(_, shares)| *shares != 0)
+            .collect::<Vec<_>>();
+
+        positions.sort();
+
+        positions
+    }
+
+    fn trade(
+        &mut self,
+        investor_id: &str,
+        asset_id: &str,
+        shares: i64,
+        cash: f32,
+    ) {
+        let Some(account) = self.accounts.get_mut(investor_id) else {
+            return;
+        };
+
+        account.cash += cash;
+
+        let position = account.positions.entry(asset_id.into()).or_insert(0);
+        *position += shares;
+
+        let holders = self.holders.entry(asset_id.into()).or_default();
+
+        if *position == 0 {
+            holders.remove(investor_id);
+        } else {
+            holders.insert(investor_id.into());
+        }
+    }
+
+    fn status(&self, equity: f32, maintenance: f32) -> MarginStatus {
+        if equity >= maintenance {
+            MarginStatus::Healthy
+        } else if equity >= maintenance * self.liquidation_level {
+            MarginStatus::MarginCall
+        } else {
+            MarginStatus::Liquidation
+        }
+    }
+}
+
+/// Equity and initial and maintenance requirements of the positions, marked
+/// at the last traded prices.
+fn evaluate(
+    cash: f32,
+    positions: &HashMap<String, i64>,
+    last_prices: &HashMap<String, f32>,
+    registry: &AssetRegistry,
+) -> (f32, f32, f32) {
+    positions.iter().fold(
+        (cash, 0.0, 0.0),
+        |(equity, initial, maintenance), (asset_id, shares)| {
+            let price = last_prices.get(asset_id).copied().unwrap_or(0.0);
+            let value = *shares as f32 * price;
+            let requirement = registry
+                .resolve(asset_id)
+                .map(|instrument| instrument.margin_requirement())
+                .unwrap_or_default();
+
+            (
+                equity + value,
+                initial + value.abs() * requirement.initial(),
+                maintenance + value.abs() * requirement.maintenance(),
+            )
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        entities::{
+            asset::Asset,
+            investor::Investor,
+            order::{Buy, Open, Order, OrderItem, Sell},
+        },
+        order_book::OrderBook,
+        registry::Instrument,
+    };
+
+    use super::*;
+
+    #[test]
+    fn call_and_liquidate_margin_accounts() {
+        let asset = Asset::new("HGLG11");
+        let registry = AssetRegistry::new().with_instrument(
+            Instrument::new("HGLG11", "CSHG Logística FII", "BRL")
+                .with_margin_requirement(MarginRequirement::new(0.5, 0.25)),
+        );
+        let foo = Investor::new("123", "Foo", vec![]);
+        let bar =
+            Investor::new("321", "Bar", vec![(asset.id().to_owned(), 1000)]);
+
+        let mut accounts = MarginAccounts::new();
+        accounts.deposit("123", 1_000.0);
+
+        let mut book = OrderBook::new(asset.id().into());
+
+        let mut trade = |buyer: &Investor, seller: &Investor, price, shares| {
+            book.submit(
+                Order::<Sell, Open>::new(
+                    asset.to_owned(),
+                    seller.to_owned(),
+                    "S",
+                    price,
+                    shares,
+                )
+                .resolve_type(),
+            )
+            .unwrap();
+
+            book.submit(
+                Order::<Buy, Open>::new(
+                    asset.to_owned(),
+                    buyer.to_owned(),
+                    "B",
+                    price,
+                    shares,
+                )
+                .resolve_type(),
+            )
+            .unwrap()
+        };
+
+        let buy = |price: f32, shares: u32| {
+            Order::<Buy, Open>::new(
+                asset.to_owned(),
+                foo.to_owned(),
+                "A",
+                price,
+                shares,
+            )
+            .resolve_type()
+        };
+
+        // "Half of the market value should be covered by the equity"
+        assert_eq!(
+            Ok(()),
+            accounts.check(&buy(10.0, 200), &HashMap::new(), &registry)
+        );
+        assert_eq!(
+            Err(MarginError::InsufficientMargin {
+                equity: 1_000.0,
+                required: 1_005.0,
+            }),
+            accounts.check(&buy(10.0, 201), &HashMap::new(), &registry)
+        );
+
+        let events = trade(&foo, &bar, 10.0, 200)
+            .iter()
+            .flat_map(|transaction| accounts.record(transaction, &registry))
+            .collect::<Vec<_>>();
+
+        assert!(events.is_empty());
+
+        // "Marking 200 shares at 6.0 leaves 200 of equity for 300 required"
+        let events = trade(&bar, &bar, 6.0, 1)
+            .iter()
+            .flat_map(|transaction| accounts.record(transaction, &registry))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![MarginSnapshot {
+                investor_id: "123".into(),
+                status: MarginStatus::MarginCall,
+                equity: 200.0,
+                initial_requirement: 600.0,
+                maintenance_requirement: 300.0,
+            }],
+            events
+        );
+
+        let events = trade(&bar, &bar, 5.0, 1)
+            .iter()
+            .flat_map(|transaction| accounts.record(transaction, &registry))
+            .collect::<Vec<_>>();
+
+        assert_eq!(MarginStatus::Liquidation, events[0].status);
+        assert_eq!(
+            Some(MarginStatus::Liquidation),
+            accounts
+                .margin("123", &registry)
+                .map(|snapshot| snapshot.status)
+        );
+
+        // "Accounts being liquidated should only reduce their positions"
+        assert_eq!(
+            Err(MarginError::Liquidating),
+            accounts.check(&buy(5.0, 1), &HashMap::new(), &registry)
+        );
+
+        let orders = accounts.liquidation_orders("123", &registry);
+
+        assert_eq!(1, orders.len());
+        assert_eq!(Side::Sell, orders[0].side());
+        assert_eq!(200, orders[0].shares());
+        assert!(orders[0].instructions().is_market());
+        assert_eq!(
+            Ok(()),
+            accounts.check(&orders[0], &HashMap::new(), &registry)
+        );
+
+        let events = trade(&bar, &bar, 10.0, 1)
+            .iter()
+            .flat_map(|transaction| accounts.record(transaction, &registry))
+            .collect::<Vec<_>>();
+
+        assert_eq!(MarginStatus::Healthy, events[0].status);
+        assert_eq!(1_000.0, events[0].equity);
+    }
+
+    #[test]
+    fn check_open_orders_of_margin_accounts() {
+        let asset = Asset::new("HGLG11");
+        let registry = AssetRegistry::new().with_instrument(
+            Instrument::new("HGLG11", "CSHG Logística FII", "BRL")
+                .with_margin_requirement(MarginRequirement::new(0.5, 0.25)),
+        );
+        let foo = Investor::new("123", "Foo", vec![]);
+
+        let mut accounts = MarginAccounts::new();
+        accounts.deposit("123", 1_000.0);
+
+        let buy = |id: &str, shares: u32| {
+            Order::<Buy, Open>::new(
+                asset.to_owned(),
+                foo.to_owned(),
+                id,
+                10.0,
+                shares,
+            )
+            .resolve_type()
+        };
+
+        let mut book = OrderBook::new(asset.id().into());
+        book.submit(buy("A", 100)).unwrap();
+
+        let mut books = HashMap::from([(asset.id().to_owned(), book)]);
+
+        // "Resting buys should use up the margin of new ones"
+        assert_eq!(Ok(()), accounts.check(&buy("B", 100), &books, &registry));
+        assert_eq!(
+            Err(MarginError::InsufficientMargin {
+                equity: 1_000.0,
+                required: 1_005.0,
+            }),
+            accounts.check(&buy("B", 101), &books, &registry)
+        );
+
+        books.get_mut("HGLG11").unwrap().cancel("A").unwrap();
+
+        assert_eq!(Ok(()), accounts.check(&buy("B", 200), &books, &registry));
+    }
+
+    #[test]
+    fn retry_unfilled_liquidations() {
+        let asset = Asset::new("HGLG11");
+        let registry = AssetRegistry::new().with_instrument(
+            Instrument::new("HGLG11", "CSHG Logística FII", "BRL")
+                .with_margin_requirement(MarginRequirement::new(0.5, 0.25)),
+        );
+        let foo = Investor::new("123", "Foo", vec![]);
+        let bar =
+            Investor::new("321", "Bar", vec![(asset.id().to_owned(), 1000)]);
+
+        let mut accounts = MarginAccounts::new();
+        accounts.deposit("123", 1_000.0);
+
+        let mut book = OrderBook::new(asset.id().into());
+
+        let sell = |price: f32, shares: u32| {
+            Order::<Sell, Open>::new(
+                asset.to_owned(),
+                bar.to_owned(),
+                "S",
+                price,
+                shares,
+            )
+            .resolve_type()
+        };
+        let buy = |investor: &Investor, price: f32, shares: u32| {
+            Order::<Buy, Open>::new(
+                asset.to_owned(),
+                investor.to_owned(),
+                "B",
+                price,
+                shares,
+            )
+            .resolve_type()
+        };
+
+        book.submit(sell(10.0, 200)).unwrap();
+        book.submit(buy(&foo, 10.0, 200)).unwrap();
+        book.submit(sell(5.0, 1)).unwrap();
+        book.submit(buy(&bar, 5.0, 1)).unwrap();
+
+        for transaction in book.history().by_asset(asset.id()) {
+            accounts.record(&transaction, &registry);
+        }
+
+        assert!(accounts.is_liquidating("123", &registry));
+
+        // "Accounts should stay liquidating while their orders cannot fill"
+        for order in accounts.liquidation_orders("123", &registry) {
+            assert!(book.submit(order).unwrap().is_empty());
+        }
+
+        assert!(accounts.is_liquidating("123", &registry));
+        assert!(book.depth(Side::Sell).is_empty());
+
+        book.submit(buy(&bar, 5.0, 200)).unwrap();
+
+        for order in accounts.liquidation_orders("123", &registry) {
+            for transaction in book.submit(order).unwrap() {
+                accounts.record(&transaction, &registry);
+            }
+        }
+
+        assert!(!accounts.is_liquidating("123", &registry));
+        assert_eq!(
+            Some(MarginStatus::Healthy),
+            accounts
+                .margin("123", &registry)
+                .map(|snapshot| snapshot.status)
+        );
+    }
+}
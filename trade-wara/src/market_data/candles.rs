@@ -0,0 +1,400 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use serde::Serialize;
+
+use crate::{dto::candle_dto::CandleDTO, entities::transaction::Transaction};
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize,
+)]
+pub enum Interval {
+    #[serde(rename = "1s")]
+    OneSecond,
+    #[serde(rename = "1m")]
+    OneMinute,
+    #[serde(rename = "5m")]
+    FiveMinutes,
+    #[serde(rename = "1h")]
+    OneHour,
+    #[serde(rename = "1d")]
+    OneDay,
+}
+
+impl Interval {
+    pub const ALL: [Interval; 5] = [
+        Interval::OneSecond,
+        Interval::OneMinute,
+        Interval::FiveMinutes,
+        Interval::OneHour,
+        Interval::OneDay,
+    ];
+
+    pub fn seconds(&self) -> i64 {
+        match self {
+            Interval::OneSecond => 1,
+            Interval::OneMinute => 60,
+            Interval::FiveMinutes => 5 * 60,
+            Interval::OneHour => 60 * 60,
+            Interval::OneDay => 24 * 60 * 60,
+        }
+    }
+
+    /// Start of the bar containing `time`, bars are aligned to UTC midnight.
+    pub fn bar_start(&self, time: DateTime<Utc>) -> DateTime<Utc> {
+        let timestamp = time.timestamp();
+
+        Utc.timestamp_opt(timestamp - timestamp.rem_euclid(self.seconds()), 0)
+            .unwrap()
+    }
+}
+
+/// Open, high, low, close and volume of an asset over an interval.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(into = "CandleDTO")]
+pub struct Candle {
+    asset_id: String,
+    interval: Interval,
+    open_time: DateTime<Utc>,
+    open: f32,
+    high: f32,
+    low: f32,
+    close: f32,
+    volume: u64,
+    turnover: f32,
+    trade_count: u64,
+}
+
+impl Candle {
+    fn new(interval: Interval, transaction: &Transaction) -> Candle {
+        Candle {
+            asset_id: transaction.asset_id().into(),
+            interval,
+            open_time: interval.bar_start(transaction.traded_at()),
+            open: transaction.price(),
+            high: transaction.price(),
+            low: transaction.price(),
+            close: transaction.price(),
+            volume: transaction.traded_shares() as u64,
+            turnover: transaction.total(),
+            trade_count: 1,
+        }
+    }
+
+    fn record(&mut self, transaction: &Transaction) {
+        self.high = self.high.max(transaction.price());
+        self.low = self.low.min(transaction.price());
+        self.close = transaction.price();
+        self.volume += transaction.traded_shares() as u64;
+        self.turnover += transaction.total();
+        self.trade_count += 1;
+    }
+
+    pub fn asset_id(&self) -> &str {
+        &self.asset_id
+    }
+
+    pub fn interval(&self) -> Interval {
+        self.interval
+    }
+
+    pub fn open_time(&self) -> DateTime<Utc> {
+        self.open_time
+    }
+
+    pub fn close_time(&self) -> DateTime<Utc> {
+        self.open_time + Duration::seconds(self.interval.seconds())
+    }
+
+    pub fn open(&self) -> f32 {
+        self.open
+    }
+
+    pub fn high(&self) -> f32 {
+        self.high
+    }
+
+    pub fn low(&self) -> f32 {
+        self.low
+    }
+
+    pub fn close(&self) -> f32 {
+        self.close
+    }
+
+    pub fn volume(&self) -> u64 {
+        self.volume
+    }
+
+    pub fn vwap(&self) -> f32 {
+        self.turnover / self.volume as f32
+    }
+
+    pub fn trade_count(&self) -> u64 {
+        self.trade_count
+    }
+}
+
+/// Builds candles from transactions. A bar stays open until the watermark,
+/// the latest trade or clock time seen, passes its close time plus the
+/// allowed lateness. Trades arriving after their bar was closed are dropped
+/// from that interval only, longer bars still open keep them.
+#[derive(Debug)]
+pub struct CandleAggregator {
+    intervals: Vec<Interval>,
+    allowed_lateness: Duration,
+    watermark: Option<DateTime<Utc>>,
+    open_bars: HashMap<(String, Interval), BTreeMap<DateTime<Utc>, Candle>>,
+    late_trades: u64,
+}
+
+impl CandleAggregator {
+    pub fn new(
+        intervals: Vec<Interval>,
+        allowed_lateness: Duration,
+    ) -> CandleAggregator {
+        CandleAggregator {
+            intervals,
+            allowed_lateness,
+            watermark: None,
+            open_bars: HashMap::new(),
+            late_trades: 0,
+        }
+    }
+
+    /// Adds the transaction to its bars, returning the bars it has closed.
+    pub fn record(&mut self, transaction: &Transaction) -> Vec<Candle> {
+        let traded_at = transaction.traded_at();
+        let mut is_late = false;
+
+        for interval in &self.intervals {
+            let closes_at =
+                Self::closes_at(*interval, traded_at, self.allowed_lateness);
+
+            if self
+                .watermark
+                .is_some_and(|watermark| closes_at <= watermark)
+            {
+                is_late = true;
+                continue;
+            }
+
+            self.open_bars
+                .entry((transaction.asset_id().into(), *interval))
+                .or_default()
+                .entry(interval.bar_start(traded_at))
+                .and_modify(|candle| candle.record(transaction))
+                .or_insert_with(|| Candle::new(*interval, transaction));
+        }
+
+        if is_late {
+            self.late_trades += 1;
+        }
+
+        self.advance(traded_at)
+    }
+
+    /// Moves the watermark forward, returning the bars closed by it.
+    pub fn advance(&mut self, now: DateTime<Utc>) -> Vec<Candle> {
+        if self.watermark.is_some_and(|watermark| watermark >= now) {
+            return vec![];
+        }
+
+        self.watermark = Some(now);
+
+        let mut closed = vec![];
+
+        for ((_, interval), bars) in self.open_bars.iter_mut() {
+            while let Some(entry) = bars.first_entry() {
+                let closes_at = Self::closes_at(
+                    *interval,
+                    *entry.key(),
+                    self.allowed_lateness,
+                );
+
+                if closes_at > now {
+                    break;
+                }
+
+                closed.push(entry.remove());
+            }
+        }
+
+        self.open_bars.retain(|_, bars| !bars.is_empty());
+
+        closed.sort_by_key(|candle| (candle.close_time(), candle.interval));
+
+        closed
+    }
+
+    /// Bars still accepting trades for an asset and interval.
+    pub fn open_bars(
+        &self,
+        asset_id: &str,
+        interval: Interval,
+    ) -> Vec<&Candle> {
+        self.open_bars
+            .get(&(asset_id.into(), interval))
+            .map(|bars| bars.values().collect())
+            .unwrap_or_default()
+    }
+
+    /// Count of trades dropped from at least one interval because their bar
+    /// was already closed.
+    pub fn late_trades(&self) -> u64 {
+        self.late_trades
+    }
+
+    fn closes_at(
+        interval: Interval,
+        time: DateTime<Utc>,
+        allowed_lateness: Duration,
+    ) -> DateTime<Utc> {
+        interval.bar_start(time)
+            + Duration::seconds(interval.seconds())
+            + allowed_lateness
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::entities::{
+        asset::Asset,
+        investor::Investor,
+        order::{Buy, Open, Order, Sell},
+    };
+
+    use super::*;
+
+    fn trade(asset_id: &str, price: f32, shares: u32, at: i64) -> Transaction {
+        let asset = Asset::new(asset_id);
+        let investor = Investor::new("123", "Joe", vec![]);
+
+        Transaction::new(
+            Order::<Buy, Open>::new(
+                asset.to_owned(),
+                investor.to_owned(),
+                "A",
+                price,
+                shares,
+            )
+            .check_order(),
+            Order::<Sell, Open>::new(asset, investor, "B", price, shares)
+                .check_order(),
+            shares,
+            price,
+        )
+        .with_traded_at(Utc.timestamp_opt(at, 0).unwrap())
+    }
+
+    #[test]
+    fn align_bars() {
+        let time = Utc.with_ymd_and_hms(2023, 8, 14, 13, 47, 31).unwrap();
+
+        assert_eq!(
+            Utc.with_ymd_and_hms(2023, 8, 14, 13, 45, 0).unwrap(),
+            Interval::FiveMinutes.bar_start(time)
+        );
+        assert_eq!(
+            Utc.with_ymd_and_hms(2023, 8, 14, 0, 0, 0).unwrap(),
+            Interval::OneDay.bar_start(time)
+        );
+    }
+
+    #[test]
+    fn aggregate_candles() {
+        let mut aggregator =
+            CandleAggregator::new(vec![Interval::OneMinute], Duration::zero());
+
+        assert!(aggregator.record(&trade("HGLG11", 5.0, 10, 60)).is_empty());
+        assert!(aggregator.record(&trade("HGLG11", 7.0, 10, 75)).is_empty());
+        assert!(aggregator.record(&trade("HGLG11", 4.0, 20, 90)).is_empty());
+        assert!(aggregator.record(&trade("MXRF11", 9.0, 1, 100)).is_empty());
+
+        let closed = aggregator.record(&trade("HGLG11", 6.0, 5, 120));
+
+        assert_eq!(2, closed.len());
+
+        let candle = closed
+            .iter()
+            .find(|candle| candle.asset_id() == "HGLG11")
+            .unwrap();
+
+        assert_eq!(Utc.timestamp_opt(60, 0).unwrap(), candle.open_time());
+        assert_eq!(
+            (5.0, 7.0, 4.0, 4.0),
+            (candle.open(), candle.high(), candle.low(), candle.close())
+        );
+        assert_eq!(40, candle.volume());
+        assert_eq!(3, candle.trade_count());
+        assert_eq!(200.0 / 40.0, candle.vwap());
+
+        assert_eq!(
+            1,
+            aggregator.open_bars("HGLG11", Interval::OneMinute).len()
+        );
+        assert!(aggregator
+            .open_bars("MXRF11", Interval::OneMinute)
+            .is_empty());
+    }
+
+    #[test]
+    fn handle_late_trades() {
+        let mut aggregator = CandleAggregator::new(
+            vec![Interval::OneSecond, Interval::OneMinute],
+            Duration::seconds(2),
+        );
+
+        aggregator.record(&trade("HGLG11", 5.0, 10, 10));
+        aggregator.record(&trade("HGLG11", 5.5, 10, 12));
+
+        // "A trade inside the allowed lateness should amend its bar"
+        assert!(aggregator.record(&trade("HGLG11", 6.0, 5, 10)).is_empty());
+
+        let closed = aggregator.advance(Utc.timestamp_opt(13, 0).unwrap());
+        assert_eq!(1, closed.len());
+        assert_eq!(15, closed[0].volume());
+        assert_eq!(6.0, closed[0].close());
+
+        // "A trade for a closed bar should still reach the open ones"
+        aggregator.record(&trade("HGLG11", 1.0, 100, 10));
+        assert_eq!(1, aggregator.late_trades());
+
+        let closed = aggregator.advance(Utc.timestamp_opt(62, 0).unwrap());
+        assert_eq!(
+            vec![Interval::OneSecond, Interval::OneMinute],
+            closed
+                .iter()
+                .map(|candle| candle.interval())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(125, closed[1].volume());
+        assert_eq!(1.0, closed[1].low());
+    }
+
+    #[test]
+    fn keep_late_trades_in_open_bars() {
+        let mut aggregator = CandleAggregator::new(
+            vec![Interval::OneMinute, Interval::OneDay],
+            Duration::zero(),
+        );
+
+        aggregator.record(&trade("HGLG11", 5.0, 10, 30));
+
+        let closed = aggregator.record(&trade("HGLG11", 6.0, 10, 90));
+        assert_eq!(1, closed.len());
+
+        // "Late for its minute, the trade should still count for the day"
+        assert!(aggregator.record(&trade("HGLG11", 4.0, 5, 45)).is_empty());
+        assert_eq!(1, aggregator.late_trades());
+
+        let day = aggregator.open_bars("HGLG11", Interval::OneDay)[0];
+        assert_eq!(25, day.volume());
+        assert_eq!(4.0, day.low());
+        assert_eq!(3, day.trade_count());
+        assert_eq!(
+            1,
+            aggregator.open_bars("HGLG11", Interval::OneMinute).len()
+        );
+    }
+}
@@ -0,0 +1,478 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::{
+    entities::order::Side, events::OrderEvent, order_book::aggregate_levels,
+    ComparableFloat,
+};
+
+/// Order-by-order market data message, modeled after ITCH. Orders are only
+/// identified by an `order_ref` so the original order ids are never exposed.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct L3Message {
+    pub sequence: u64,
+    pub asset_id: String,
+    #[serde(flatten)]
+    pub body: L3Body,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type")]
+pub enum L3Body {
+    Add {
+        order_ref: u64,
+        side: Side,
+        price: f32,
+        shares: u32,
+    },
+    Execute {
+        order_ref: u64,
+        price: f32,
+        shares: u32,
+    },
+    Reduce {
+        order_ref: u64,
+        shares: u32,
+    },
+    Delete {
+        order_ref: u64,
+    },
+    /// Replaces the order with a new one, at the back of its price level.
+    Replace {
+        order_ref: u64,
+        new_order_ref: u64,
+        price: f32,
+        shares: u32,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ReconstructionError {
+    DuplicatedOrderRef(u64),
+    UnknownOrderRef(u64),
+    OutRangeShareCount(u64),
+}
+
+/// Translates the events of every `OrderBook` into a single L3 stream.
+#[derive(Debug, Default)]
+pub struct L3Feed {
+    sequence: u64,
+    next_order_ref: u64,
+    order_refs: HashMap<String, u64>,
+}
+
+impl L3Feed {
+    pub fn new() -> L3Feed {
+        L3Feed::default()
+    }
+
+    /// Returns `None` for events of orders the feed has never seen added.
+    pub fn process(
+        &mut self,
+        asset_id: &str,
+        event: &OrderEvent,
+    ) -> Option<L3Message> {
+        let body = match event {
+            OrderEvent::Added {
+                order_id,
+                side,
+                price,
+                shares,
+            } => {
+                self.next_order_ref += 1;
+                self.order_refs
+                    .insert(order_id.to_owned(), self.next_order_ref);
+
+                L3Body::Add {
+                    order_ref: self.next_order_ref,
+                    side: *side,
+                    price: *price,
+                    shares: *shares,
+                }
+            }
+            OrderEvent::Executed {
+                order_id,
+                price,
+                shares,
+                remaining,
+                ..
+            } => L3Body::Execute {
+                order_ref: self.order_ref(order_id, *remaining == 0)?,
+                price: *price,
+                shares: *shares,
+            },
+            OrderEvent::Reduced {
+                order_id,
+                shares,
+                remaining,
+                ..
+            } => L3Body::Reduce {
+                order_ref: self.order_ref(order_id, *remaining == 0)?,
+                shares: *shares,
+            },
+            OrderEvent::Deleted { order_id, .. } => L3Body::Delete {
+                order_ref: self.order_ref(order_id, true)?,
+            },
+            OrderEvent::Replaced {
+                order_id,
+                price,
+                shares,
+                ..
+            } => {
+                let order_ref = self.order_ref(order_id, true)?;
+
+                self.next_order_ref += 1;
+                self.order_refs
+                    .insert(order_id.to_owned(), self.next_order_ref);
+
+                L3Body::Replace {
+                    order_ref,
+                    new_order_ref: self.next_order_ref,
+                    price: *price,
+                    shares: *shares,
+                }
+            }
+        };
+
+        self.sequence += 1;
+
+        Some(L3Message {
+            sequence: self.sequence,
+            asset_id: asset_id.into(),
+            body,
+        })
+    }
+
+    fn order_ref(&mut self, order_id: &str, release: bool) -> Option<u64> {
+        if release {
+            return self.order_refs.remove(order_id);
+        }
+
+        self.order_refs.get(order_id).copied()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RestingOrder {
+    pub side: Side,
+    pub price: f32,
+    pub shares: u32,
+}
+
+/// Rebuilds the resting orders of a single asset from its L3 messages.
+#[derive(Debug)]
+pub struct BookReconstructor {
+    asset_id: String,
+    orders: HashMap<u64, RestingOrder>,
+}
+
+impl BookReconstructor {
+    pub fn new(asset_id: &str) -> BookReconstructor {
+        BookReconstructor {
+            asset_id: asset_id.into(),
+            orders: HashMap::new(),
+        }
+    }
+
+    /// Messages from other assets are ignored.
+    pub fn apply(
+        &mut self,
+        message: &L3Message,
+    ) -> Result<(), ReconstructionError> {
+        if message.asset_id != self.asset_id {
+            return Ok(());
+        }
+
+        match message.body {
+            L3Body::Add {
+                order_ref,
+                side,
+                price,
+                shares,
+            } => {
+                if self.orders.contains_key(&order_ref) {
+                    return Err(ReconstructionError::DuplicatedOrderRef(
+                        order_ref,
+                    ));
+                }
+
+                self.orders.insert(
+                    order_ref,
+                    RestingOrder {
+                        side,
+                        price,
+                        shares,
+                    },
+                );
+            }
+            L3Body::Execute {
+                order_ref, shares, ..
+            }
+            | L3Body::Reduce { order_ref, shares } => {
+                let order = self
+                    .orders
+                    .get_mut(&order_ref)
+                    .ok_or(ReconstructionError::UnknownOrderRef(order_ref))?;
+
+                if order.shares < shares {
+                    return Err(ReconstructionError::OutRangeShareCount(
+                        order_ref,
+                    ));
+                }
+
+                order.shares -= shares;
+
+                if order.shares == 0 {
+                    self.orders.remove(&order_ref);
+                }
+            }
+            L3Body::Delete { order_ref } => {
+                self.orders
+                    .remove(&order_ref)
+                    .ok_or(ReconstructionError::UnknownOrderRef(order_ref))?;
+            }
+            L3Body::Replace {
+                order_ref,
+                new_order_ref,
+                price,
+                shares,
+            } => {
+                if self.orders.contains_key(&new_order_ref) {
+                    return Err(ReconstructionError::DuplicatedOrderRef(
+                        new_order_ref,
+                    ));
+                }
+
+                let order = self
+                    .orders
+                    .remove(&order_ref)
+                    .ok_or(ReconstructionError::UnknownOrderRef(order_ref))?;
+
+                self.orders.insert(
+                    new_order_ref,
+                    RestingOrder {
+                        price,
+                        shares,
+                        ..order
+                    },
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn orders(&self) -> &HashMap<u64, RestingOrder> {
+        &self.orders
+    }
+
+    /// Aggregated shares by price level, best price first.
+    pub fn depth(&self, side: Side) -> Vec<(f32, u32)> {
+        let mut levels = self
+            .orders
+            .values()
+            .filter(|order| order.side == side)
+            .map(|order| (order.price, order.shares))
+            .collect::<Vec<_>>();
+
+        levels.sort_by_key(|(price, _)| ComparableFloat(*price));
+
+        if side == Side::Buy {
+            levels.reverse();
+        }
+
+        aggregate_levels(levels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        entities::{
+            asset::Asset,
+            investor::Investor,
+            order::{Buy, Open, Order, OrderItem, Sell},
+        },
+        events::CancelReason,
+        order_book::OrderBook,
+    };
+
+    use super::*;
+
+    fn publish(
+        feed: &mut L3Feed,
+        book: &mut OrderBook,
+        reconstructor: &mut BookReconstructor,
+    ) -> Vec<L3Message> {
+        let asset_id = book.asset_id().to_owned();
+
+        book.drain_events()
+            .iter()
+            .filter_map(|event| feed.process(&asset_id, event))
+            .inspect(|message| reconstructor.apply(message).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn reconstruct_book_from_feed() {
+        let asset = Asset::new("HGLG11");
+        let mut book = OrderBook::new(asset.id().to_owned());
+        let mut feed = L3Feed::new();
+        let mut reconstructor = BookReconstructor::new(asset.id());
+
+        let seller = Investor::new("123", "Foo", vec![("HGLG11".into(), 100)]);
+        let buyer = Investor::new("321", "Bar", vec![]);
+
+        let orders = [
+            Order::<Sell, Open>::new(
+                asset.to_owned(),
+                seller.to_owned(),
+                "A",
+                5.0,
+                10,
+            )
+            .resolve_type(),
+            Order::<Sell, Open>::new(
+                asset.to_owned(),
+                seller.to_owned(),
+                "B",
+                5.5,
+                20,
+            )
+            .resolve_type(),
+            Order::<Sell, Open>::new(
+                asset.to_owned(),
+                seller.to_owned(),
+                "C",
+                5.5,
+                5,
+            )
+            .resolve_type(),
+            Order::<Buy, Open>::new(
+                asset.to_owned(),
+                buyer.to_owned(),
+                "D",
+                4.5,
+                15,
+            )
+            .resolve_type(),
+            Order::<Buy, Open>::new(
+                asset.to_owned(),
+                buyer.to_owned(),
+                "E",
+                5.0,
+                4,
+            )
+            .resolve_type(),
+        ];
+
+        for order in orders {
+            book.append(order).unwrap();
+        }
+
+        assert_eq!(5, publish(&mut feed, &mut book, &mut reconstructor).len());
+
+        // "E" partially executes "A"
+        book.try_match().unwrap();
+        book.reduce("D", 5).unwrap();
+        book.cancel("B").unwrap();
+
+        let messages = publish(&mut feed, &mut book, &mut reconstructor);
+
+        assert_eq!(4, messages.len());
+        assert!(matches!(
+            messages[0].body,
+            L3Body::Execute { shares: 4, .. }
+        ));
+        assert!(matches!(messages[2].body, L3Body::Reduce { shares: 5, .. }));
+        assert!(matches!(messages[3].body, L3Body::Delete { .. }));
+
+        assert_eq!(vec![(4.5, 10)], reconstructor.depth(Side::Buy));
+        assert_eq!(vec![(5.0, 6), (5.5, 5)], reconstructor.depth(Side::Sell));
+
+        assert_eq!(book.depth(Side::Buy), reconstructor.depth(Side::Buy));
+        assert_eq!(book.depth(Side::Sell), reconstructor.depth(Side::Sell));
+        assert_eq!(3, reconstructor.orders().len());
+    }
+
+    #[test]
+    fn sequence_and_anonymise_orders() {
+        let mut feed = L3Feed::new();
+
+        let added = feed
+            .process(
+                "HGLG11",
+                &OrderEvent::Added {
+                    order_id: "a16a766e".into(),
+                    side: Side::Buy,
+                    price: 5.0,
+                    shares: 10,
+                },
+            )
+            .unwrap();
+
+        let deleted = feed
+            .process(
+                "HGLG11",
+                &OrderEvent::Deleted {
+                    order_id: "a16a766e".into(),
+                    side: Side::Buy,
+                    reason: CancelReason::Requested,
+                },
+            )
+            .unwrap();
+
+        assert_eq!((1, 2), (added.sequence, deleted.sequence));
+        assert_eq!(L3Body::Delete { order_ref: 1 }, deleted.body);
+
+        // "Once deleted the order reference should be released"
+        let deleted_again = OrderEvent::Deleted {
+            order_id: "a16a766e".into(),
+            side: Side::Buy,
+            reason: CancelReason::Requested,
+        };
+        assert_eq!(None, feed.process("HGLG11", &deleted_again));
+
+        let json = serde_json::to_value(&added).unwrap();
+        assert_eq!("Add", json["type"]);
+        assert_eq!(1, json["order_ref"]);
+        assert!(json.get("order_id").is_none());
+    }
+
+    #[test]
+    fn reject_inconsistent_messages() {
+        let mut reconstructor = BookReconstructor::new("HGLG11");
+
+        let message = |body| L3Message {
+            sequence: 1,
+            asset_id: "HGLG11".into(),
+            body,
+        };
+
+        assert_eq!(
+            Err(ReconstructionError::UnknownOrderRef(7)),
+            reconstructor.apply(&message(L3Body::Delete { order_ref: 7 }))
+        );
+
+        let add = message(L3Body::Add {
+            order_ref: 7,
+            side: Side::Sell,
+            price: 5.0,
+            shares: 3,
+        });
+
+        assert_eq!(Ok(()), reconstructor.apply(&add));
+        assert_eq!(
+            Err(ReconstructionError::DuplicatedOrderRef(7)),
+            reconstructor.apply(&add)
+        );
+        assert_eq!(
+            Err(ReconstructionError::OutRangeShareCount(7)),
+            reconstructor.apply(&message(L3Body::Reduce {
+                order_ref: 7,
+                shares: 4,
+            }))
+        );
+    }
+}
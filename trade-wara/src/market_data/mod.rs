@@ -0,0 +1,3 @@
+pub mod candles;
+pub mod level3;
+pub mod ticker;
@@ -0,0 +1,136 @@
+use serde::Serialize;
+
+use crate::entities::transaction::Transaction;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Quote {
+    pub price: f32,
+    pub shares: u32,
+}
+
+/// Top of book and last trade summary of an asset.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Ticker {
+    asset_id: String,
+    bid: Option<Quote>,
+    ask: Option<Quote>,
+    last_price: Option<f32>,
+    last_shares: Option<u32>,
+    volume: u64,
+    trade_count: u64,
+}
+
+impl Ticker {
+    pub fn new(asset_id: &str) -> Ticker {
+        Ticker {
+            asset_id: asset_id.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Returns whether the best bid or ask has changed.
+    pub fn update_quote(
+        &mut self,
+        bid: Option<Quote>,
+        ask: Option<Quote>,
+    ) -> bool {
+        if self.bid == bid && self.ask == ask {
+            return false;
+        }
+
+        self.bid = bid;
+        self.ask = ask;
+
+        true
+    }
+
+    pub fn record_trade(&mut self, transaction: &Transaction) {
+        self.last_price = Some(transaction.price());
+        self.last_shares = Some(transaction.traded_shares());
+        self.volume += transaction.traded_shares() as u64;
+        self.trade_count += 1;
+    }
+
+    pub fn asset_id(&self) -> &str {
+        &self.asset_id
+    }
+
+    pub fn bid(&self) -> Option<&Quote> {
+        self.bid.as_ref()
+    }
+
+    pub fn ask(&self) -> Option<&Quote> {
+        self.ask.as_ref()
+    }
+
+    pub fn last_price(&self) -> Option<f32> {
+        self.last_price
+    }
+
+    pub fn last_shares(&self) -> Option<u32> {
+        self.last_shares
+    }
+
+    pub fn volume(&self) -> u64 {
+        self.volume
+    }
+
+    pub fn trade_count(&self) -> u64 {
+        self.trade_count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::entities::{
+        asset::Asset,
+        investor::Investor,
+        order::{Buy, Open, Order, Sell},
+    };
+
+    use super::*;
+
+    #[test]
+    fn update_quote() {
+        let mut ticker = Ticker::new("HGLG11");
+        let bid = Quote {
+            price: 5.0,
+            shares: 10,
+        };
+
+        assert!(ticker.update_quote(Some(bid), None));
+        assert!(!ticker.update_quote(Some(bid), None));
+        assert_eq!(Some(&bid), ticker.bid());
+        assert_eq!(None, ticker.ask());
+    }
+
+    #[test]
+    fn record_trade() {
+        let asset = Asset::new("HGLG11");
+        let investor = Investor::new("123", "Joe", vec![]);
+
+        let transaction = Transaction::new(
+            Order::<Buy, Open>::new(
+                asset.to_owned(),
+                investor.to_owned(),
+                "A",
+                5.5,
+                10,
+            )
+            .check_order(),
+            Order::<Sell, Open>::new(asset, investor, "B", 5.5, 10)
+                .check_order(),
+            10,
+            5.5,
+        );
+
+        let mut ticker = Ticker::new("HGLG11");
+        ticker.record_trade(&transaction);
+        ticker.record_trade(&transaction);
+
+        assert_eq!(Some(5.5), ticker.last_price());
+        assert_eq!(Some(10), ticker.last_shares());
+        assert_eq!(20, ticker.volume());
+        assert_eq!(2, ticker.trade_count());
+    }
+}
@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    entities::order::Side, events::CancelReason, order_book::OrderBook,
+};
+
+/// Cancels many orders at once across every book, received on the orders
+/// topic alongside the orders.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command")]
+pub enum MassCancel {
+    /// Every order of an investor, whatever its asset.
+    CancelInvestorOrders { investor_id: String },
+    /// Every order of an asset.
+    CancelAssetOrders { asset_id: String },
+    /// Every order sent through a session that disconnected.
+    CancelSessionOrders { session_id: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CancelledOrder {
+    pub asset_id: String,
+    pub order_id: String,
+    pub side: Side,
+}
+
+/// Orders removed by a mass cancel, sorted by asset.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MassCancelSummary {
+    pub request: MassCancel,
+    pub cancelled_orders: Vec<CancelledOrder>,
+}
+
+/// Orders removed by an instrument update, such as the dark orders left
+/// when its dark pool is disabled.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InstrumentCancelSummary {
+    pub asset_id: String,
+    pub reason: CancelReason,
+    pub cancelled_orders: Vec<CancelledOrder>,
+}
+
+impl MassCancel {
+    pub fn reason(&self) -> CancelReason {
+        match self {
+            MassCancel::CancelSessionOrders { .. } => {
+                CancelReason::Disconnected
+            }
+            _ => CancelReason::MassCancel,
+        }
+    }
+
+    /// Id of the investor, asset or session covered by the request.
+    pub fn target_id(&self) -> &str {
+        match self {
+            MassCancel::CancelInvestorOrders { investor_id } => investor_id,
+            MassCancel::CancelAssetOrders { asset_id } => asset_id,
+            MassCancel::CancelSessionOrders { session_id } => session_id,
+        }
+    }
+
+    /// Cancels the orders of every book covered by the request, each book
+    /// recording an event for every order removed.
+    pub fn apply(
+        &self,
+        books: &mut HashMap<String, OrderBook>,
+    ) -> MassCancelSummary {
+        let mut cancelled_orders = vec![];
+
+        for (asset_id, book) in books.iter_mut() {
+            let cancelled = match self {
+                MassCancel::CancelInvestorOrders { investor_id } => book
+                    .cancel_where(
+                        |order_investor_id, _| order_investor_id == investor_id,
+                        self.reason(),
+                    ),
+                MassCancel::CancelAssetOrders {
+                    asset_id: cancelled_asset_id,
+                } if asset_id == cancelled_asset_id => {
+                    book.cancel_where(|_, _| true, self.reason())
+                }
+                MassCancel::CancelAssetOrders { .. } => vec![],
+                MassCancel::CancelSessionOrders { session_id } => book
+                    .cancel_where(
+                        |_, instructions| {
+                            instructions.session_id() == Some(session_id)
+                        },
+                        self.reason(),
+                    ),
+            };
+
+            cancelled_orders.extend(cancelled.into_iter().map(
+                |(order_id, side)| CancelledOrder {
+                    asset_id: asset_id.to_owned(),
+                    order_id,
+                    side,
+                },
+            ));
+        }
+
+        cancelled_orders.sort_by(|a, b| a.asset_id.cmp(&b.asset_id));
+
+        MassCancelSummary {
+            request: self.to_owned(),
+            cancelled_orders,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        entities::{
+            asset::Asset,
+            investor::Investor,
+            order::{Buy, Open, Order, OrderInstructions, OrderItem, Sell},
+        },
+        events::OrderEvent,
+    };
+
+    use super::*;
+
+    #[test]
+    fn deserialize_mass_cancel() {
+        let json = r#"{
+            "command": "CancelSessionOrders",
+            "session_id": "fix-01"
+        }"#;
+
+        assert_eq!(
+            MassCancel::CancelSessionOrders {
+                session_id: "fix-01".into()
+            },
+            serde_json::from_str(json).unwrap()
+        );
+    }
+
+    #[test]
+    fn cancel_across_books() {
+        let mut books = HashMap::new();
+        let foo = Investor::new(
+            "123",
+            "Foo",
+            vec![("HGLG11".into(), 100), ("MXRF11".into(), 100)],
+        );
+        let bar = Investor::new("321", "Bar", vec![]);
+
+        for asset_id in ["HGLG11", "MXRF11"] {
+            let asset = Asset::new(asset_id);
+            let mut book = OrderBook::new(asset_id.into());
+
+            book.submit(
+                Order::<Sell, Open>::new(
+                    asset.to_owned(),
+                    foo.to_owned(),
+                    &format!("{}-S", asset_id),
+                    6.0,
+                    10,
+                )
+                .with_instructions(match asset_id {
+                    "HGLG11" => {
+                        OrderInstructions::new().with_session_id("fix-01")
+                    }
+                    _ => OrderInstructions::new(),
+                })
+                .resolve_type(),
+            )
+            .unwrap();
+            book.submit(
+                Order::<Buy, Open>::new(
+                    asset,
+                    bar.to_owned(),
+                    &format!("{}-B", asset_id),
+                    5.0,
+                    10,
+                )
+                .resolve_type(),
+            )
+            .unwrap();
+            book.drain_events();
+
+            books.insert(asset_id.to_owned(), book);
+        }
+
+        let summary = MassCancel::CancelSessionOrders {
+            session_id: "fix-01".into(),
+        }
+        .apply(&mut books);
+
+        assert_eq!(1, summary.cancelled_orders.len());
+        assert_eq!("HGLG11-S", summary.cancelled_orders[0].order_id);
+        assert_eq!(
+            vec![OrderEvent::Deleted {
+                order_id: "HGLG11-S".into(),
+                side: Side::Sell,
+                reason: CancelReason::Disconnected,
+            }],
+            books.get_mut("HGLG11").unwrap().drain_events()
+        );
+
+        let summary = MassCancel::CancelInvestorOrders {
+            investor_id: "123".into(),
+        }
+        .apply(&mut books);
+
+        assert_eq!(1, summary.cancelled_orders.len());
+        assert_eq!("MXRF11-S", summary.cancelled_orders[0].order_id);
+        assert_eq!(
+            CancelReason::MassCancel,
+            match books.get_mut("MXRF11").unwrap().drain_events()[..] {
+                [OrderEvent::Deleted { reason, .. }] => reason,
+                _ => panic!("Expected a single deleted order"),
+            }
+        );
+
+        let summary = MassCancel::CancelAssetOrders {
+            asset_id: "MXRF11".into(),
+        }
+        .apply(&mut books);
+
+        assert_eq!(1, summary.cancelled_orders.len());
+        assert_eq!("MXRF11-B", summary.cancelled_orders[0].order_id);
+        assert_eq!(vec![(5.0, 10)], books["HGLG11"].depth(Side::Buy));
+        assert!(books["MXRF11"].depth(Side::Buy).is_empty());
+    }
+}
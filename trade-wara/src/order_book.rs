@@ -1,15 +1,30 @@
 use std::{
     cmp::{self, Reverse},
-    collections::BinaryHeap,
+    collections::{BinaryHeap, VecDeque},
     sync::Arc,
 };
 
-use crate::entities::{
-    order::{
-        Buy, Open, Order, OrderError, OrderResolution, OrderTransition,
-        OrderType, Sell,
+use serde::Deserialize;
+
+use crate::{
+    allocation::{self, MatchingPolicy},
+    auction::{self, Equilibrium},
+    circuit_breaker::PriceBands,
+    dark_book::DarkBook,
+    entities::{
+        asset::{ReferenceData, ReferenceDataError},
+        investor::Investor,
+        order::{
+            Buy, Open, Order, OrderError, OrderInstructions, OrderResolution,
+            OrderTransition, OrderType, PostOnly, Sell, Side,
+        },
+        transaction::Transaction,
     },
-    transaction::Transaction,
+    events::{CancelReason, MarketEvent, OrderEvent},
+    history::TradeHistory,
+    market_data::ticker::{Quote, Ticker},
+    session::{SessionPhase, TradingSession},
+    ComparableFloat,
 };
 
 #[derive(Debug, Default)]
@@ -17,7 +32,163 @@ pub struct OrderBook {
     asset_id: String,
     buy_orders: BinaryHeap<Order<Buy, Open>>,
     sell_orders: BinaryHeap<Reverse<Order<Sell, Open>>>,
-    transactions: Vec<Arc<Transaction>>,
+    transactions: TradeHistory,
+    events: Vec<OrderEvent>,
+    ticker: Ticker,
+    ticker_changed: bool,
+    mode: MatchingMode,
+    indicative: Option<Equilibrium>,
+    market_events: Vec<MarketEvent>,
+    session: TradingSession,
+    price_bands: PriceBands,
+    reference_price: Option<f32>,
+    reference_data: ReferenceData,
+    queued_orders: Option<Vec<OrderResolution>>,
+    self_trade_prevention: Option<SelfTradePrevention>,
+    stop_orders: Vec<OrderResolution>,
+    dark_book: Option<DarkBook>,
+    matching_policy: MatchingPolicy,
+    matched: VecDeque<Arc<Transaction>>,
+}
+
+/// Best buy and sell orders set aside while the orders behind them match.
+type ParkedOrders = (Vec<Order<Buy, Open>>, Vec<Reverse<Order<Sell, Open>>>);
+
+/// Shares a resting order may trade with an all-or-none or minimum quantity
+/// order crossing it.
+struct Counterparty {
+    pending_shares: u32,
+    reserve_shares: u32,
+    min_execution: u32,
+}
+
+impl Counterparty {
+    fn new<T: OrderType>(order: &Order<T, Open>) -> Counterparty {
+        Counterparty {
+            pending_shares: *order.pending_shares(),
+            reserve_shares: order.reserve_shares(),
+            min_execution: order.min_execution(),
+        }
+    }
+
+    /// Icebergs replenish while matching, so their reserve fills as well.
+    fn fillable_shares(counterparties: &[Counterparty]) -> u32 {
+        counterparties
+            .iter()
+            .map(|counterparty| {
+                counterparty.pending_shares + counterparty.reserve_shares
+            })
+            .sum()
+    }
+
+    /// Whether an order with `pending_shares` can trade at least
+    /// `min_execution` of them at once with any of the counterparties.
+    fn can_execute(
+        counterparties: &[Counterparty],
+        pending_shares: u32,
+        min_execution: u32,
+    ) -> bool {
+        counterparties.iter().any(|counterparty| {
+            let shares = cmp::min(pending_shares, counterparty.pending_shares);
+
+            shares >= min_execution && shares >= counterparty.min_execution
+        })
+    }
+}
+
+/// Shares of the open orders of an investor in a book, by side.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct OpenShares {
+    pub buy: u32,
+    pub sell: u32,
+    /// Shares short sales would borrow, beyond the holdings of the investor.
+    pub short: u32,
+}
+
+impl OpenShares {
+    fn add<T: OrderType>(&mut self, order: &Order<T, Open>) {
+        let shares = *order.pending_shares() + order.reserve_shares();
+
+        match T::SIDE {
+            Side::Buy => self.buy += shares,
+            Side::Sell => self.sell += shares,
+        }
+
+        if T::SIDE == Side::Sell && order.instructions().is_short_sell() {
+            let held = order
+                .investor()
+                .assets()
+                .get(order.asset().id())
+                .copied()
+                .unwrap_or(0);
+
+            self.short += shares.saturating_sub(held);
+        }
+    }
+
+    fn add_resolution(&mut self, order: &OrderResolution) {
+        match order {
+            OrderResolution::Buy(OrderTransition::Open(order)) => {
+                self.add(order)
+            }
+            OrderResolution::Sell(OrderTransition::Open(order)) => {
+                self.add(order)
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Operator halt of a book, which may cancel every resting order and queue
+/// the orders received until trading resumes instead of rejecting them.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Halt {
+    reason: Option<String>,
+    cancel_all: bool,
+    queue_orders: bool,
+}
+
+impl Halt {
+    pub fn new() -> Halt {
+        Halt::default()
+    }
+
+    pub fn with_reason(mut self, reason: &str) -> Halt {
+        self.reason = Some(reason.into());
+        self
+    }
+
+    pub fn with_cancel_all(mut self) -> Halt {
+        self.cancel_all = true;
+        self
+    }
+
+    pub fn with_queued_orders(mut self) -> Halt {
+        self.queue_orders = true;
+        self
+    }
+}
+
+/// What happens when the best buy and sell orders that cross belong to the
+/// same investor, instead of trading with each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum SelfTradePrevention {
+    CancelNewest,
+    CancelOldest,
+    CancelBoth,
+    /// Removes the shares of the smaller order from the larger one and
+    /// cancels the smaller.
+    DecrementAndCancel,
+}
+
+/// Continuous books match on every order, while books in a call auction only
+/// accumulate orders until they are uncrossed at a single price.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MatchingMode {
+    #[default]
+    Continuous,
+    Auction,
 }
 
 #[derive(Debug, PartialEq)]
@@ -25,6 +196,20 @@ pub enum OrderBookError {
     InvalidOrderAssetId,
     InvalidOrderState,
     NoMatchingOrderAvailable,
+    OrderNotFound,
+    AuctionInProgress,
+    AuctionNotStarted,
+    PhaseNotAcceptingOrders(SessionPhase),
+    PriceOutOfBand { low: f32, high: f32 },
+    InvalidTickSize(f32),
+    InvalidLotSize(u32),
+    QuantityBelowMinimum(u32),
+    QuantityAboveMaximum(u32),
+    NotionalAboveMaximum(f32),
+    VolatilityAuctionStarted,
+    PostOnlyWouldCross,
+    NoPegReference,
+    DarkPoolUnavailable,
     MatchingError(String),
 }
 
@@ -38,291 +223,3545 @@ impl From<OrderError> for OrderBookError {
     }
 }
 
+impl From<ReferenceDataError> for OrderBookError {
+    fn from(value: ReferenceDataError) -> Self {
+        match value {
+            ReferenceDataError::InvalidTickSize(tick_size) => {
+                Self::InvalidTickSize(tick_size)
+            }
+            ReferenceDataError::InvalidLotSize(round_lot) => {
+                Self::InvalidLotSize(round_lot)
+            }
+            ReferenceDataError::QuantityBelowMinimum(min_quantity) => {
+                Self::QuantityBelowMinimum(min_quantity)
+            }
+            ReferenceDataError::QuantityAboveMaximum(max_quantity) => {
+                Self::QuantityAboveMaximum(max_quantity)
+            }
+            ReferenceDataError::NotionalAboveMaximum(max_notional) => {
+                Self::NotionalAboveMaximum(max_notional)
+            }
+        }
+    }
+}
+
 impl OrderBook {
     pub fn new(asset_id: String) -> OrderBook {
         OrderBook {
+            ticker: Ticker::new(&asset_id),
             asset_id,
             ..Default::default()
         }
     }
 
+    /// Replaces the default bounded transaction history.
+    pub fn with_history(mut self, history: TradeHistory) -> OrderBook {
+        self.transactions = history;
+        self
+    }
+
+    /// Trades by the given session instead of continuously, entering a call
+    /// auction right away when the session is in one.
+    pub fn with_session(mut self, session: TradingSession) -> OrderBook {
+        self.session = session;
+
+        if self.session.phase().is_auction() {
+            self.start_auction();
+        }
+
+        self
+    }
+
+    /// Checks incoming orders against the tick, lot and size rules of the
+    /// asset. Books of fractional assets should use `ReferenceData::fractional`.
+    pub fn with_reference_data(
+        mut self,
+        reference_data: ReferenceData,
+    ) -> OrderBook {
+        self.reference_data = reference_data;
+        self
+    }
+
+    pub fn reference_data(&self) -> &ReferenceData {
+        &self.reference_data
+    }
+
+    pub fn with_price_bands(mut self, price_bands: PriceBands) -> OrderBook {
+        self.price_bands = price_bands;
+        self
+    }
+
+    /// Prevents investors from trading with themselves during continuous
+    /// matching. Auctions still uncross orders of the same investor.
+    pub fn with_self_trade_prevention(
+        mut self,
+        self_trade_prevention: SelfTradePrevention,
+    ) -> OrderBook {
+        self.self_trade_prevention = Some(self_trade_prevention);
+        self
+    }
+
+    /// Allocates incoming orders across the orders resting at the best
+    /// price, price-time priority being the default.
+    pub fn with_matching_policy(
+        mut self,
+        matching_policy: MatchingPolicy,
+    ) -> OrderBook {
+        self.matching_policy = matching_policy;
+        self
+    }
+
+    pub fn matching_policy(&self) -> MatchingPolicy {
+        self.matching_policy
+    }
+
+    /// Accepts dark orders, crossing them at the midpoint of the book.
+    pub fn with_dark_book(mut self) -> OrderBook {
+        self.dark_book = Some(DarkBook::new());
+        self
+    }
+
+    pub fn dark_book(&self) -> Option<&DarkBook> {
+        self.dark_book.as_ref()
+    }
+
+    /// Replaces the trading rules of the book. Disabling the dark pool
+    /// cancels the orders resting in it, returning their ids and sides.
+    pub fn update_rules(
+        &mut self,
+        reference_data: ReferenceData,
+        price_bands: PriceBands,
+        self_trade_prevention: Option<SelfTradePrevention>,
+        dark_pool: bool,
+        matching_policy: MatchingPolicy,
+    ) -> Vec<(String, Side)> {
+        self.reference_data = reference_data;
+        self.price_bands = price_bands;
+        self.self_trade_prevention = self_trade_prevention;
+        self.matching_policy = matching_policy;
+
+        match dark_pool {
+            true => {
+                self.dark_book.get_or_insert_with(DarkBook::new);
+                vec![]
+            }
+            false => self.dark_book.take().map_or(vec![], |mut dark_book| {
+                dark_book.cancel_where(|_, _| true)
+            }),
+        }
+    }
+
+    /// Sets the price the static band is placed around, such as the previous
+    /// close. The last traded price is used while it is not set.
+    pub fn set_reference_price(&mut self, reference_price: f32) {
+        self.reference_price = Some(reference_price);
+    }
+
+    pub fn reference_price(&self) -> Option<f32> {
+        self.reference_price.or(self.ticker.last_price())
+    }
+
+    /// Orders are checked against the phase set by the last session update,
+    /// so `update_session` should be called before appending.
     pub fn append(
         &mut self,
         order: OrderResolution,
     ) -> Result<(), OrderBookError> {
+        if !self.session.phase().accepts_orders() {
+            // Queued orders are only validated once trading resumes
+            if let (SessionPhase::Halted, Some(queued_orders)) =
+                (self.session.phase(), self.queued_orders.as_mut())
+            {
+                queued_orders.push(order);
+                return Ok(());
+            }
+
+            return Err(OrderBookError::PhaseNotAcceptingOrders(
+                self.session.phase(),
+            ));
+        }
+
+        // Market orders have no price to take part in an equilibrium
+        if order.instructions().is_market()
+            && self.mode == MatchingMode::Auction
+        {
+            return Err(OrderBookError::AuctionInProgress);
+        }
+
+        let is_stop = order.instructions().is_stop();
+
         match order {
             OrderResolution::Sell(order) => {
                 let order = self.check_is_order_valid(order)?;
 
+                if is_stop {
+                    self.push_stop(OrderResolution::Sell(
+                        OrderTransition::Open(order),
+                    ));
+                    return Ok(());
+                }
+
+                if order.instructions().is_dark() {
+                    self.dark_book
+                        .as_mut()
+                        .ok_or(OrderBookError::DarkPoolUnavailable)?
+                        .push_sell(order);
+                    return Ok(());
+                }
+
+                let order = self.apply_peg(order)?;
+                let order = self.apply_post_only(order)?;
+
+                // Market orders never rest, so they are not displayed
+                if !order.instructions().is_market() {
+                    self.events.push(Self::added_event(&order));
+                }
+
                 self.sell_orders.push(Reverse(order));
             }
             OrderResolution::Buy(order) => {
                 let order = self.check_is_order_valid(order)?;
 
+                if is_stop {
+                    self.push_stop(OrderResolution::Buy(
+                        OrderTransition::Open(order),
+                    ));
+                    return Ok(());
+                }
+
+                if order.instructions().is_dark() {
+                    self.dark_book
+                        .as_mut()
+                        .ok_or(OrderBookError::DarkPoolUnavailable)?
+                        .push_buy(order);
+                    return Ok(());
+                }
+
+                let order = self.apply_peg(order)?;
+                let order = self.apply_post_only(order)?;
+
+                if !order.instructions().is_market() {
+                    self.events.push(Self::added_event(&order));
+                }
+
                 self.buy_orders.push(order);
             }
         }
 
+        self.repeg();
+        self.refresh_market_data();
+
         Ok(())
     }
 
-    pub fn try_match(&mut self) -> Result<Arc<Transaction>, OrderBookError> {
-        let Some(mut sell_order) = self.sell_orders.peek_mut() else {
-            return Err(OrderBookError::NoMatchingOrderAvailable)
-        };
+    /// Appends the order and matches the book, triggering every stop order
+    /// reached by the resulting trades. Shares of market orders left unfilled
+    /// are cancelled.
+    pub fn submit(
+        &mut self,
+        order: OrderResolution,
+    ) -> Result<Vec<Arc<Transaction>>, OrderBookError> {
+        self.append(order)?;
 
-        let Some(mut buy_order) = self.buy_orders.peek_mut() else {
-            return Err(OrderBookError::NoMatchingOrderAvailable);
-        };
+        Ok(self.match_book())
+    }
 
-        // In order to match, buy order's price should be greater or equal than sell order's price
-        if buy_order.price() < sell_order.0.price() {
-            return Err(OrderBookError::NoMatchingOrderAvailable);
+    pub fn stop_orders(&self) -> &[OrderResolution] {
+        &self.stop_orders
+    }
+
+    /// Removes a resting order from the book, whatever its side. Dark orders
+    /// are removed without any event.
+    pub fn cancel(&mut self, order_id: &str) -> Result<(), OrderBookError> {
+        if self
+            .dark_book
+            .as_mut()
+            .is_some_and(|dark_book| dark_book.cancel(order_id))
+        {
+            return Ok(());
         }
 
-        // TODO: Get the difference between Buy and sell prices as Platform commission
-        let traded_price = *buy_order.price();
+        if let Some(index) = self
+            .stop_orders
+            .iter()
+            .position(|order| order.order_id() == order_id)
+        {
+            let order = self.stop_orders.remove(index);
 
-        let common_shares_count = cmp::min(
-            *sell_order.0.pending_shares(),
-            *buy_order.pending_shares(),
-        );
+            self.events.push(OrderEvent::Deleted {
+                order_id: order_id.into(),
+                side: order.side(),
+                reason: CancelReason::Requested,
+            });
 
-        let sell_order = match sell_order.0.sell(common_shares_count)? {
-            OrderTransition::Closed(_) => {
-                drop(sell_order);
+            return Ok(());
+        }
 
-                self.sell_orders.pop().unwrap().0.check_order()
-            }
-            other => other,
-        };
+        let buy_count = self.buy_orders.len();
+        self.buy_orders.retain(|order| order.id() != order_id);
 
-        let buy_order = match buy_order.buy(common_shares_count)? {
-            OrderTransition::Closed(_) => {
-                drop(buy_order);
+        if self.buy_orders.len() < buy_count {
+            self.events.push(OrderEvent::Deleted {
+                order_id: order_id.into(),
+                side: Side::Buy,
+                reason: CancelReason::Requested,
+            });
+            self.repeg();
+            self.refresh_market_data();
 
-                self.buy_orders.pop().unwrap().check_order()
-            }
-            other => other,
-        };
+            return Ok(());
+        }
 
-        let transaction = Arc::new(Transaction::new(
-            buy_order,
-            sell_order,
-            common_shares_count,
-            traded_price,
-        ));
+        let sell_count = self.sell_orders.len();
+        self.sell_orders.retain(|order| order.0.id() != order_id);
 
-        self.transactions.push(transaction.clone());
+        if self.sell_orders.len() < sell_count {
+            self.events.push(OrderEvent::Deleted {
+                order_id: order_id.into(),
+                side: Side::Sell,
+                reason: CancelReason::Requested,
+            });
+            self.repeg();
+            self.refresh_market_data();
 
-        Ok(transaction.clone())
+            return Ok(());
+        }
+
+        Err(OrderBookError::OrderNotFound)
     }
 
-    fn check_is_order_valid<T: OrderType>(
-        &self,
-        order: OrderTransition<T>,
-    ) -> Result<Order<T, Open>, OrderBookError> {
-        match order {
-            OrderTransition::Open(order) => {
-                if *order.asset().id() != self.asset_id {
-                    return Err(OrderBookError::InvalidOrderAssetId);
-                }
+    /// Removes every order whose investor id and instructions are accepted by
+    /// `filter`, including stop, dark and queued orders, returning their ids
+    /// and sides. Dark and queued orders are removed without any event.
+    pub fn cancel_where(
+        &mut self,
+        filter: impl Fn(&str, &OrderInstructions) -> bool,
+        reason: CancelReason,
+    ) -> Vec<(String, Side)> {
+        let mut cancelled = vec![];
 
-                Ok(order)
-            }
-            OrderTransition::Closed(_) => {
-                return Err(OrderBookError::InvalidOrderState);
-            }
-        }
-    }
-}
+        self.stop_orders.retain(|order| {
+            let cancel = filter(order.investor_id(), order.instructions());
 
-#[cfg(test)]
-mod tests {
-    use crate::entities::{asset::Asset, investor::Investor, order::OrderItem};
+            if cancel {
+                cancelled.push((order.order_id().to_owned(), order.side()));
+            }
 
-    use super::*;
+            !cancel
+        });
 
-    #[test]
-    fn append_orders() {
-        let asset_1 = Asset::new("HGLG11".into());
-        let asset_2 = Asset::new("WRONG11".into());
+        self.buy_orders.retain(|order| {
+            let cancel = filter(order.investor().id(), order.instructions());
 
-        let mut book = OrderBook::new(asset_1.id().to_owned());
+            if cancel {
+                cancelled.push((order.id().to_owned(), Side::Buy));
+            }
 
-        let investor = Investor::new(
-            "123".into(),
-            "Foo".into(),
-            vec![(asset_1.id().to_owned(), 10)],
-        );
+            !cancel
+        });
 
-        let sell_order = Order::<Sell, Open>::new(
-            asset_1.to_owned(),
-            investor.to_owned(),
-            "A".into(),
-            5.0,
-            10,
-        );
+        self.sell_orders.retain(|order| {
+            let cancel =
+                filter(order.0.investor().id(), order.0.instructions());
 
-        let sell_order_2 = Order::<Sell, Open>::new(
-            asset_1.to_owned(),
-            investor.to_owned(),
-            "A".into(),
-            3.0,
-            10,
-        );
+            if cancel {
+                cancelled.push((order.0.id().to_owned(), Side::Sell));
+            }
 
-        let buy_order = Order::<Buy, Open>::new(
-            asset_1.to_owned(),
-            investor.to_owned(),
-            "B".into(),
-            3.0,
-            10,
-        );
+            !cancel
+        });
 
-        let buy_order_2 = Order::<Buy, Open>::new(
-            asset_1.to_owned(),
-            investor.to_owned(),
-            "C".into(),
-            5.0,
-            10,
-        );
+        for (order_id, side) in cancelled.iter() {
+            self.events.push(OrderEvent::Deleted {
+                order_id: order_id.to_owned(),
+                side: *side,
+                reason,
+            });
+        }
 
-        let wrong_order = Order::<Buy, Open>::new(
-            asset_2.to_owned(),
-            investor.to_owned(),
-            "D".into(),
-            1.0,
-            10,
-        );
+        if let Some(queued_orders) = self.queued_orders.as_mut() {
+            queued_orders.retain(|order| {
+                let cancel = filter(order.investor_id(), order.instructions());
 
-        assert!(book.append(sell_order.resolve_type()).is_ok());
-        assert!(book.append(sell_order_2.resolve_type()).is_ok());
-        assert!(book.append(buy_order.resolve_type()).is_ok());
-        assert!(book.append(buy_order_2.resolve_type()).is_ok());
+                if cancel {
+                    cancelled.push((order.order_id().to_owned(), order.side()));
+                }
 
-        assert_eq!(
-            OrderBookError::InvalidOrderAssetId,
-            book.append(wrong_order.resolve_type()).unwrap_err()
-        );
+                !cancel
+            });
+        }
 
-        assert_eq!(2, book.sell_orders.len());
-        assert_eq!(2, book.buy_orders.len());
+        if let Some(dark_book) = self.dark_book.as_mut() {
+            cancelled.extend(dark_book.cancel_where(filter));
+        }
 
-        // Buy orders with increased prices should have higher priority
-        assert_eq!(&buy_order_2, book.buy_orders.peek().unwrap());
+        self.repeg();
+        self.refresh_market_data();
 
-        // Sell orders with lowest prices should have higher priority
-        assert_eq!(&Reverse(sell_order_2), book.sell_orders.peek().unwrap());
+        cancelled
     }
 
-    #[test]
-    fn match_orders() {
-        const ORDER_PRICE: f32 = 5.0;
-        const INCREASED_ORDER_PRICE: f32 = ORDER_PRICE + 0.5;
+    /// Counts the resting, stop, dark and queued orders of an investor.
+    pub fn open_orders(&self, investor_id: &str) -> usize {
+        let stop_orders = self
+            .stop_orders
+            .iter()
+            .filter(|order| order.investor_id() == investor_id)
+            .count();
+        let buy_orders = self
+            .buy_orders
+            .iter()
+            .filter(|order| order.investor().id() == investor_id)
+            .count();
+        let sell_orders = self
+            .sell_orders
+            .iter()
+            .filter(|order| order.0.investor().id() == investor_id)
+            .count();
+        let queued_orders = self.queued_orders.as_ref().map_or(0, |orders| {
+            orders
+                .iter()
+                .filter(|order| order.investor_id() == investor_id)
+                .count()
+        });
+        let dark_orders = self.dark_book.as_ref().map_or(0, |dark_book| {
+            dark_book
+                .buy_orders()
+                .iter()
+                .filter(|order| order.investor().id() == investor_id)
+                .count()
+                + dark_book
+                    .sell_orders()
+                    .iter()
+                    .filter(|order| order.investor().id() == investor_id)
+                    .count()
+        });
 
-        const ORDER_QUANTITY: u32 = 10;
-        const PARTIAL_QUANTITY: u32 = ORDER_QUANTITY / 2;
+        stop_orders + buy_orders + sell_orders + queued_orders + dark_orders
+    }
 
-        let asset = Asset::new("HGLG11".into());
-        let mut book = OrderBook::new(asset.id().to_owned());
+    /// Sums the shares left to trade of the resting, stop, dark and queued
+    /// orders of an investor.
+    pub fn open_shares(&self, investor_id: &str) -> OpenShares {
+        let mut open = OpenShares::default();
 
-        let investor_a = Investor::new(
-            "123".into(),
-            "Foo".into(),
-            vec![(asset.id().to_owned(), 10)],
+        for order in self
+            .stop_orders
+            .iter()
+            .chain(self.queued_orders.iter().flat_map(|orders| orders.iter()))
+        {
+            if order.investor_id() == investor_id {
+                open.add_resolution(order);
+            }
+        }
+
+        let dark_book = self.dark_book.as_ref();
+        let buy_orders = self.buy_orders.iter().chain(
+            dark_book
+                .into_iter()
+                .flat_map(|book| book.buy_orders().iter()),
+        );
+        let sell_orders = self.sell_orders.iter().map(|order| &order.0).chain(
+            dark_book
+                .into_iter()
+                .flat_map(|book| book.sell_orders().iter()),
         );
 
-        let investor_b = Investor::new("321".into(), "Bar".into(), vec![]);
+        for order in buy_orders {
+            if order.investor().id() == investor_id {
+                open.add(order);
+            }
+        }
 
-        let mut order_a = Order::<Sell, Open>::new(
+        for order in sell_orders {
+            if order.investor().id() == investor_id {
+                open.add(order);
+            }
+        }
+
+        open
+    }
+
+    /// Decreases the pending shares of a resting order keeping its priority.
+    pub fn reduce(
+        &mut self,
+        order_id: &str,
+        share_count: u32,
+    ) -> Result<(), OrderBookError> {
+        let mut buy_orders = std::mem::take(&mut self.buy_orders).into_vec();
+        let result = Self::reduce_order(&mut buy_orders, order_id, share_count);
+        self.buy_orders = buy_orders.into();
+
+        let event = match result {
+            Err(OrderBookError::OrderNotFound) => {
+                let mut sell_orders = std::mem::take(&mut self.sell_orders)
+                    .into_vec()
+                    .into_iter()
+                    .map(|order| order.0)
+                    .collect::<Vec<_>>();
+
+                let result =
+                    Self::reduce_order(&mut sell_orders, order_id, share_count);
+
+                self.sell_orders =
+                    sell_orders.into_iter().map(Reverse).collect();
+
+                result?
+            }
+            other => other?,
+        };
+
+        self.events.push(event);
+        self.repeg();
+        self.refresh_market_data();
+
+        Ok(())
+    }
+
+    /// Drains the events recorded since the last call, oldest first.
+    pub fn drain_events(&mut self) -> Vec<OrderEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Drains the market events recorded since the last call, oldest first.
+    pub fn drain_market_events(&mut self) -> Vec<MarketEvent> {
+        std::mem::take(&mut self.market_events)
+    }
+
+    /// Moves the book to the phase scheduled by its session, returning the
+    /// transactions of an auction closed by the transition.
+    pub fn update_session(
+        &mut self,
+    ) -> Result<Vec<Arc<Transaction>>, OrderBookError> {
+        let transition = self.session.update();
+        self.apply_transition(transition)
+    }
+
+    /// Stops trading until `resume`, regardless of the session calendar.
+    pub fn halt(
+        &mut self,
+        halt: Halt,
+    ) -> Result<Vec<Arc<Transaction>>, OrderBookError> {
+        let transition = self.session.halt();
+        let transactions = self.apply_transition(transition)?;
+
+        let cancelled_orders = match halt.cancel_all {
+            true => self.cancel_all(CancelReason::MarketHalt),
+            false => 0,
+        };
+
+        if halt.queue_orders {
+            self.queued_orders.get_or_insert_with(Vec::new);
+        }
+
+        self.market_events.push(MarketEvent::TradingHalted {
+            asset_id: self.asset_id.to_owned(),
+            reason: halt.reason,
+            cancelled_orders,
+            queue_orders: self.queued_orders.is_some(),
+        });
+
+        Ok(transactions)
+    }
+
+    /// Returns to the scheduled phase, appending the orders queued during the
+    /// halt and matching them when trading continuously.
+    pub fn resume(&mut self) -> Result<Vec<Arc<Transaction>>, OrderBookError> {
+        if self.session.phase() != SessionPhase::Halted {
+            return Ok(vec![]);
+        }
+
+        let transition = self.session.resume();
+        let mut transactions = self.apply_transition(transition)?;
+
+        let queued_orders = self.queued_orders.take().unwrap_or_default();
+        let released_orders = queued_orders.len();
+        let rejected_orders = queued_orders
+            .into_iter()
+            .map(|order| self.append(order))
+            .filter(Result::is_err)
+            .count();
+
+        transactions.extend(self.match_book());
+
+        self.market_events.push(MarketEvent::TradingResumed {
+            asset_id: self.asset_id.to_owned(),
+            released_orders,
+            rejected_orders,
+        });
+
+        Ok(transactions)
+    }
+
+    /// Cancels every resting and stop order, returning how many were
+    /// cancelled.
+    pub fn cancel_all(&mut self, reason: CancelReason) -> usize {
+        let buy_orders = std::mem::take(&mut self.buy_orders).into_vec();
+        let sell_orders = std::mem::take(&mut self.sell_orders).into_vec();
+        let stop_orders = std::mem::take(&mut self.stop_orders);
+        let dark_orders = self.dark_book.as_mut().map_or(0, DarkBook::clear);
+        let cancelled_orders = buy_orders.len()
+            + sell_orders.len()
+            + stop_orders.len()
+            + dark_orders;
+
+        for order in stop_orders {
+            self.events.push(OrderEvent::Deleted {
+                order_id: order.order_id().into(),
+                side: order.side(),
+                reason,
+            });
+        }
+
+        for order in buy_orders {
+            self.events.push(OrderEvent::Deleted {
+                order_id: order.id().into(),
+                side: Side::Buy,
+                reason,
+            });
+        }
+
+        for Reverse(order) in sell_orders {
+            self.events.push(OrderEvent::Deleted {
+                order_id: order.id().into(),
+                side: Side::Sell,
+                reason,
+            });
+        }
+
+        self.repeg();
+        self.refresh_market_data();
+
+        cancelled_orders
+    }
+
+    pub fn phase(&self) -> SessionPhase {
+        self.session.phase()
+    }
+
+    /// Whether market orders can trade, the book matching continuously.
+    pub fn accepts_market_orders(&self) -> bool {
+        self.session.phase() == SessionPhase::Continuous
+            && self.mode == MatchingMode::Continuous
+    }
+
+    /// Stops continuous matching, orders keep resting until `uncross`.
+    pub fn start_auction(&mut self) {
+        if self.mode == MatchingMode::Auction {
+            return;
+        }
+
+        self.mode = MatchingMode::Auction;
+        self.indicative = None;
+        self.market_events.push(MarketEvent::AuctionStarted {
+            asset_id: self.asset_id.to_owned(),
+        });
+
+        self.refresh_market_data();
+    }
+
+    /// Executes every crossing order at the equilibrium price and moves the
+    /// book back to continuous matching. All-or-none and minimum quantity
+    /// orders do not take part, they keep resting for continuous matching.
+    /// Sell orders that cannot be delivered are cancelled, the orders behind
+    /// them trading instead while they cross the equilibrium price.
+    pub fn uncross(&mut self) -> Result<Vec<Arc<Transaction>>, OrderBookError> {
+        if self.mode != MatchingMode::Auction {
+            return Err(OrderBookError::AuctionNotStarted);
+        }
+
+        let equilibrium = self.indicative_equilibrium();
+        let (buy_orders, sell_orders) = self.park_constrained();
+
+        // Trades refresh the quotes of the continuous book, not the
+        // indicative uncross
+        self.mode = MatchingMode::Continuous;
+
+        let transactions = equilibrium
+            .map_or(vec![], |equilibrium| self.execute_uncross(equilibrium));
+
+        self.buy_orders.extend(buy_orders);
+        self.sell_orders.extend(sell_orders);
+
+        self.indicative = None;
+        self.market_events.push(MarketEvent::AuctionUncrossed {
+            asset_id: self.asset_id.to_owned(),
+            equilibrium,
+        });
+
+        if self.repeg() {
+            self.refresh_market_data();
+        }
+
+        Ok(transactions)
+    }
+
+    /// Price and volume the book would uncross at, using the last traded
+    /// price as reference. All-or-none and minimum quantity orders are left
+    /// out.
+    pub fn indicative_equilibrium(&self) -> Option<Equilibrium> {
+        let unconstrained =
+            |instructions: &OrderInstructions| !instructions.is_constrained();
+
+        auction::equilibrium(
+            &self.depth_where(Side::Buy, unconstrained),
+            &self.depth_where(Side::Sell, unconstrained),
+            self.ticker.last_price(),
+        )
+    }
+
+    pub fn mode(&self) -> MatchingMode {
+        self.mode
+    }
+
+    /// Aggregated pending shares by price level, best price first.
+    pub fn depth(&self, side: Side) -> Vec<(f32, u32)> {
+        self.depth_where(side, |_| true)
+    }
+
+    fn depth_where(
+        &self,
+        side: Side,
+        include: impl Fn(&OrderInstructions) -> bool,
+    ) -> Vec<(f32, u32)> {
+        let mut levels = match side {
+            Side::Buy => self
+                .buy_orders
+                .iter()
+                .filter(|order| include(order.instructions()))
+                .map(|order| (*order.price(), *order.pending_shares()))
+                .collect::<Vec<_>>(),
+            Side::Sell => self
+                .sell_orders
+                .iter()
+                .filter(|order| include(order.0.instructions()))
+                .map(|order| (*order.0.price(), *order.0.pending_shares()))
+                .collect::<Vec<_>>(),
+        };
+
+        levels.sort_by_key(|(price, _)| ComparableFloat(*price));
+
+        if side == Side::Buy {
+            levels.reverse();
+        }
+
+        aggregate_levels(levels)
+    }
+
+    pub fn asset_id(&self) -> &str {
+        &self.asset_id
+    }
+
+    pub fn history(&self) -> &TradeHistory {
+        &self.transactions
+    }
+
+    pub fn ticker(&self) -> &Ticker {
+        &self.ticker
+    }
+
+    /// Returns the ticker when it has changed since the last call.
+    pub fn take_ticker_update(&mut self) -> Option<Ticker> {
+        if !std::mem::take(&mut self.ticker_changed) {
+            return None;
+        }
+
+        Some(self.ticker.clone())
+    }
+
+    /// Trades the best crossing orders, skipping the all-or-none and minimum
+    /// quantity orders that cannot trade without losing their priority.
+    /// Pro-rata policies trade a whole price level at once, the transactions
+    /// after the first one are returned by the following calls.
+    pub fn try_match(&mut self) -> Result<Arc<Transaction>, OrderBookError> {
+        if let Some(transaction) = self.matched.pop_front() {
+            return Ok(transaction);
+        }
+
+        if self.mode == MatchingMode::Auction {
+            return Err(OrderBookError::AuctionInProgress);
+        }
+
+        let (buy_orders, sell_orders) = self.park_unmatchable();
+        let parked = !buy_orders.is_empty() || !sell_orders.is_empty();
+
+        let result = self.match_top();
+
+        self.buy_orders.extend(buy_orders);
+        self.sell_orders.extend(sell_orders);
+
+        if parked {
+            self.refresh_market_data();
+        }
+
+        // The auction starts with the parked orders back in the book
+        if let Err(OrderBookError::VolatilityAuctionStarted) = result {
+            let transition = self.session.start_volatility_auction(
+                self.price_bands.volatility_auction(),
+            );
+            self.apply_transition(transition)?;
+        }
+
+        let mut transactions = result?.into_iter();
+        let transaction = transactions
+            .next()
+            .ok_or(OrderBookError::NoMatchingOrderAvailable)?;
+
+        self.matched.extend(transactions);
+
+        Ok(transaction)
+    }
+
+    fn match_top(&mut self) -> Result<Vec<Arc<Transaction>>, OrderBookError> {
+        let Some(sell_order) = self.sell_orders.peek() else {
+            return Err(OrderBookError::NoMatchingOrderAvailable);
+        };
+
+        let Some(buy_order) = self.buy_orders.peek() else {
+            return Err(OrderBookError::NoMatchingOrderAvailable);
+        };
+
+        // In order to match, buy order's price should be greater or equal than sell order's price
+        if buy_order.price() < sell_order.0.price() {
+            return Err(OrderBookError::NoMatchingOrderAvailable);
+        }
+
+        let is_self_trade =
+            buy_order.investor().id() == sell_order.0.investor().id();
+
+        // TODO: Get the difference between Buy and sell prices as Platform commission
+        let traded_price = match (
+            buy_order.instructions().is_market(),
+            sell_order.0.instructions().is_market(),
+        ) {
+            (false, _) => *buy_order.price(),
+            (true, false) => *sell_order.0.price(),
+            (true, true) => match self.ticker.last_price() {
+                Some(last_price) => last_price,
+                None => return Err(OrderBookError::NoMatchingOrderAvailable),
+            },
+        };
+
+        if let (true, Some(mode)) = (is_self_trade, self.self_trade_prevention)
+        {
+            self.prevent_self_trade(mode);
+
+            // Orders behind the cancelled ones may still match
+            return self.match_top();
+        }
+
+        if self
+            .price_bands
+            .breaches_dynamic(traded_price, self.ticker.last_price())
+        {
+            return Err(OrderBookError::VolatilityAuctionStarted);
+        }
+
+        match self.matching_policy {
+            MatchingPolicy::Fifo => {
+                Ok(vec![self.execute_top(traded_price, u32::MAX)?])
+            }
+            policy => self.execute_level(policy, traded_price),
+        }
+    }
+
+    /// Trades the best order of the newest side against the orders resting
+    /// at the best price of the other side, as allocated by `policy`. Levels
+    /// with all-or-none or minimum quantity orders trade in price-time
+    /// priority instead.
+    fn execute_level(
+        &mut self,
+        policy: MatchingPolicy,
+        traded_price: f32,
+    ) -> Result<Vec<Arc<Transaction>>, OrderBookError> {
+        let (Some(buy_order), Some(Reverse(sell_order))) =
+            (self.buy_orders.peek(), self.sell_orders.peek())
+        else {
+            return Err(OrderBookError::NoMatchingOrderAvailable);
+        };
+
+        let mut transactions = vec![];
+        let mut error = None;
+
+        if buy_order.sequence() < sell_order.sequence() {
+            let shares = *sell_order.pending_shares();
+            let investor_id = sell_order.investor().id().to_owned();
+            let price = *buy_order.price();
+
+            let mut level = vec![];
+            while self
+                .buy_orders
+                .peek()
+                .is_some_and(|order| *order.price() == price)
+            {
+                level.extend(self.buy_orders.pop());
+            }
+
+            let Some(allocations) =
+                self.allocate(policy, shares, &level, &investor_id)
+            else {
+                self.buy_orders.extend(level);
+                return Ok(vec![self.execute_top(traded_price, u32::MAX)?]);
+            };
+
+            let mut rest = vec![];
+
+            for (order, shares) in level.into_iter().zip(allocations) {
+                if shares == 0 || error.is_some() {
+                    rest.push(order);
+                    continue;
+                }
+
+                let order_id = order.id().to_owned();
+                self.buy_orders.push(order);
+
+                match self.execute_top(traded_price, shares) {
+                    Ok(transaction) => transactions.push(transaction),
+                    Err(err) => error = Some(err),
+                }
+
+                if self
+                    .buy_orders
+                    .peek()
+                    .is_some_and(|order| order.id() == order_id)
+                {
+                    rest.extend(self.buy_orders.pop());
+                }
+            }
+
+            self.buy_orders.extend(rest);
+        } else {
+            let shares = *buy_order.pending_shares();
+            let investor_id = buy_order.investor().id().to_owned();
+            let price = *sell_order.price();
+
+            let mut level = vec![];
+            while self
+                .sell_orders
+                .peek()
+                .is_some_and(|order| *order.0.price() == price)
+            {
+                level.extend(self.sell_orders.pop().map(|order| order.0));
+            }
+
+            let Some(allocations) =
+                self.allocate(policy, shares, &level, &investor_id)
+            else {
+                self.sell_orders.extend(level.into_iter().map(Reverse));
+                return Ok(vec![self.execute_top(traded_price, u32::MAX)?]);
+            };
+
+            let mut rest = vec![];
+
+            for (order, shares) in level.into_iter().zip(allocations) {
+                if shares == 0 || error.is_some() {
+                    rest.push(Reverse(order));
+                    continue;
+                }
+
+                let order_id = order.id().to_owned();
+                self.sell_orders.push(Reverse(order));
+
+                match self.execute_top(traded_price, shares) {
+                    Ok(transaction) => transactions.push(transaction),
+                    Err(err) => error = Some(err),
+                }
+
+                if self
+                    .sell_orders
+                    .peek()
+                    .is_some_and(|order| order.0.id() == order_id)
+                {
+                    rest.extend(self.sell_orders.pop());
+                }
+            }
+
+            self.sell_orders.extend(rest);
+        }
+
+        self.refresh_market_data();
+
+        // The order that failed was cancelled, the trades before it are kept
+        match error {
+            Some(err) if transactions.is_empty() => Err(err),
+            _ => Ok(transactions),
+        }
+    }
+
+    /// Shares of the incoming order of `investor_id` allocated to each order
+    /// of a level, leaving out the orders self-trade prevention would cancel.
+    fn allocate<T: OrderType>(
+        &self,
+        policy: MatchingPolicy,
+        shares: u32,
+        level: &[Order<T, Open>],
+        investor_id: &str,
+    ) -> Option<Vec<u32>> {
+        if level
+            .iter()
+            .any(|order| order.instructions().is_constrained())
+        {
+            return None;
+        }
+
+        let pending_shares = level
+            .iter()
+            .map(|order| {
+                match self.self_trade_prevention.is_some()
+                    && order.investor().id() == investor_id
+                {
+                    true => 0,
+                    false => *order.pending_shares(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        Some(allocation::allocate(policy, shares, &pending_shares))
+    }
+
+    /// Matches while the book crosses, then injects the stop orders triggered
+    /// by the last traded price one at a time, oldest first, matching again
+    /// after each of them so that stops can trigger further stops.
+    /// Sell orders that cannot be delivered are cancelled as they are
+    /// reached, matching carries on with the orders behind them.
+    fn match_book(&mut self) -> Vec<Arc<Transaction>> {
+        let mut transactions = vec![];
+
+        loop {
+            loop {
+                if self.repeg() {
+                    self.refresh_market_data();
+                }
+
+                match self.try_match() {
+                    Ok(transaction) => transactions.push(transaction),
+                    Err(OrderBookError::MatchingError(_)) => continue,
+                    Err(_) => break,
+                }
+            }
+
+            self.cancel_unfilled_market_orders();
+            transactions.extend(self.cross_dark_book());
+
+            let Some(mut order) = self.next_triggered_stop() else {
+                return transactions;
+            };
+
+            order.trigger_stop();
+
+            let (order_id, side) = (order.order_id().to_owned(), order.side());
+
+            if self.append(order).is_err() {
+                self.events.push(OrderEvent::Deleted {
+                    order_id,
+                    side,
+                    reason: CancelReason::Rejected,
+                });
+            }
+        }
+    }
+
+    /// Crosses the dark book at the midpoint of the lit book, only while the
+    /// book trades continuously. Dark trades do not move the ticker.
+    fn cross_dark_book(&mut self) -> Vec<Arc<Transaction>> {
+        if self.mode == MatchingMode::Auction
+            || self.session.phase() != SessionPhase::Continuous
+        {
+            return vec![];
+        }
+
+        let (Some(bid), Some(ask)) = self.lit_quote() else {
+            return vec![];
+        };
+
+        let traded_at = self.session.now();
+        let Some(dark_book) = self.dark_book.as_mut() else {
+            return vec![];
+        };
+
+        let transactions = dark_book
+            .cross((bid + ask) / 2.0, traded_at)
+            .into_iter()
+            .map(Arc::new)
+            .collect::<Vec<_>>();
+
+        for transaction in transactions.iter() {
+            self.transactions.record(transaction.clone());
+        }
+
+        transactions
+    }
+
+    /// Trailing stops start following the prices from the last trade.
+    fn push_stop(&mut self, mut order: OrderResolution) {
+        if let Some(last_price) = self.ticker.last_price() {
+            order.track_price(last_price);
+        }
+
+        self.stop_orders.push(order);
+    }
+
+    fn next_triggered_stop(&mut self) -> Option<OrderResolution> {
+        if self.mode == MatchingMode::Auction
+            || self.session.phase() != SessionPhase::Continuous
+        {
+            return None;
+        }
+
+        let last_price = self.ticker.last_price()?;
+        let index = self
+            .stop_orders
+            .iter()
+            .position(|order| order.is_stop_triggered(last_price))?;
+
+        Some(self.stop_orders.remove(index))
+    }
+
+    /// Market orders always rank first, so only the best orders are checked.
+    fn cancel_unfilled_market_orders(&mut self) {
+        let mut cancelled = false;
+
+        while self
+            .buy_orders
+            .peek()
+            .is_some_and(|order| order.instructions().is_market())
+        {
+            let order = self.buy_orders.pop().unwrap();
+
+            self.events.push(OrderEvent::Deleted {
+                order_id: order.id().into(),
+                side: Side::Buy,
+                reason: CancelReason::Unfilled,
+            });
+            cancelled = true;
+        }
+
+        while self
+            .sell_orders
+            .peek()
+            .is_some_and(|order| order.0.instructions().is_market())
+        {
+            let Reverse(order) = self.sell_orders.pop().unwrap();
+
+            self.events.push(OrderEvent::Deleted {
+                order_id: order.id().into(),
+                side: Side::Sell,
+                reason: CancelReason::Unfilled,
+            });
+            cancelled = true;
+        }
+
+        if cancelled {
+            self.repeg();
+            self.refresh_market_data();
+        }
+    }
+
+    /// Removes shares of the best buy and sell orders of the same investor
+    /// instead of trading them.
+    fn prevent_self_trade(&mut self, mode: SelfTradePrevention) {
+        let (Some(mut buy_order), Some(mut sell_order)) =
+            (self.buy_orders.peek_mut(), self.sell_orders.peek_mut())
+        else {
+            return;
+        };
+
+        let buy_is_newest = buy_order.sequence() > sell_order.0.sequence();
+        let buy_shares = *buy_order.pending_shares();
+        let sell_shares = *sell_order.0.pending_shares();
+
+        let (cancel_buy, cancel_sell) = match mode {
+            SelfTradePrevention::CancelNewest => {
+                (buy_is_newest, !buy_is_newest)
+            }
+            SelfTradePrevention::CancelOldest => {
+                (!buy_is_newest, buy_is_newest)
+            }
+            SelfTradePrevention::CancelBoth => (true, true),
+            SelfTradePrevention::DecrementAndCancel => {
+                (buy_shares <= sell_shares, sell_shares <= buy_shares)
+            }
+        };
+
+        if mode == SelfTradePrevention::DecrementAndCancel {
+            if !cancel_buy && buy_order.reduce(sell_shares).is_ok() {
+                self.events.push(OrderEvent::Reduced {
+                    order_id: buy_order.id().into(),
+                    side: Side::Buy,
+                    shares: sell_shares,
+                    remaining: *buy_order.pending_shares(),
+                    reason: CancelReason::SelfTradePrevention,
+                });
+            }
+
+            if !cancel_sell && sell_order.0.reduce(buy_shares).is_ok() {
+                self.events.push(OrderEvent::Reduced {
+                    order_id: sell_order.0.id().into(),
+                    side: Side::Sell,
+                    shares: buy_shares,
+                    remaining: *sell_order.0.pending_shares(),
+                    reason: CancelReason::SelfTradePrevention,
+                });
+            }
+        }
+
+        drop(buy_order);
+        drop(sell_order);
+
+        if cancel_buy {
+            if let Some(order) = self.buy_orders.pop() {
+                self.events.push(OrderEvent::Deleted {
+                    order_id: order.id().into(),
+                    side: Side::Buy,
+                    reason: CancelReason::SelfTradePrevention,
+                });
+            }
+        }
+
+        if cancel_sell {
+            if let Some(Reverse(order)) = self.sell_orders.pop() {
+                self.events.push(OrderEvent::Deleted {
+                    order_id: order.id().into(),
+                    side: Side::Sell,
+                    reason: CancelReason::SelfTradePrevention,
+                });
+            }
+        }
+
+        self.refresh_market_data();
+    }
+
+    /// Takes out of the book the best orders that cannot trade with each
+    /// other, so the orders behind them can trade without losing priority:
+    /// all-or-none orders the crossing orders cannot fill in full, and
+    /// counterparties too small for the minimum quantity of an order, or the
+    /// order itself when no crossing order is large enough. Two all-or-none
+    /// orders only trade with each other for the same shares.
+    fn park_unmatchable(&mut self) -> ParkedOrders {
+        let mut buy_orders = vec![];
+        let mut sell_orders = vec![];
+
+        while let (Some(buy_order), Some(Reverse(sell_order))) =
+            (self.buy_orders.peek(), self.sell_orders.peek())
+        {
+            if buy_order.price() < sell_order.price() {
+                break;
+            }
+
+            let buy_shares = *buy_order.pending_shares();
+            let sell_shares = *sell_order.pending_shares();
+            let buy_is_aon = buy_order.instructions().is_all_or_none();
+            let sell_is_aon = sell_order.instructions().is_all_or_none();
+            let buy_counterparties = self.counterparties(
+                Side::Buy,
+                *buy_order.price(),
+                buy_order.investor().id(),
+            );
+            let sell_counterparties = self.counterparties(
+                Side::Sell,
+                *sell_order.price(),
+                sell_order.investor().id(),
+            );
+
+            let mut park_buy = buy_is_aon
+                && Counterparty::fillable_shares(&buy_counterparties)
+                    < buy_shares;
+            let mut park_sell = sell_is_aon
+                && Counterparty::fillable_shares(&sell_counterparties)
+                    < sell_shares;
+
+            if buy_is_aon
+                && sell_is_aon
+                && !park_buy
+                && !park_sell
+                && buy_shares != sell_shares
+            {
+                park_buy = buy_shares > sell_shares;
+                park_sell = !park_buy;
+            }
+
+            let shares = cmp::min(buy_shares, sell_shares);
+
+            if !park_buy && !park_sell && shares < buy_order.min_execution() {
+                park_buy = !Counterparty::can_execute(
+                    &buy_counterparties,
+                    buy_shares,
+                    buy_order.min_execution(),
+                );
+                park_sell = !park_buy;
+            } else if !park_buy
+                && !park_sell
+                && shares < sell_order.min_execution()
+            {
+                park_sell = !Counterparty::can_execute(
+                    &sell_counterparties,
+                    sell_shares,
+                    sell_order.min_execution(),
+                );
+                park_buy = !park_sell;
+            }
+
+            if !park_buy && !park_sell {
+                break;
+            }
+
+            if park_buy {
+                buy_orders.extend(self.buy_orders.pop());
+            }
+
+            if park_sell {
+                sell_orders.extend(self.sell_orders.pop());
+            }
+        }
+
+        (buy_orders, sell_orders)
+    }
+
+    /// Takes the all-or-none and minimum quantity orders out of the book.
+    fn park_constrained(&mut self) -> ParkedOrders {
+        let (constrained, buy_orders): (Vec<_>, Vec<_>) =
+            std::mem::take(&mut self.buy_orders)
+                .into_iter()
+                .partition(|order| order.instructions().is_constrained());
+        self.buy_orders = buy_orders.into();
+
+        let (sell_constrained, sell_orders): (Vec<_>, Vec<_>) =
+            std::mem::take(&mut self.sell_orders)
+                .into_iter()
+                .partition(|order| order.0.instructions().is_constrained());
+        self.sell_orders = sell_orders.into();
+
+        (constrained, sell_constrained)
+    }
+
+    /// Trades the best orders at the equilibrium price until its volume is
+    /// executed, or until the best orders left no longer cross it.
+    fn execute_uncross(
+        &mut self,
+        equilibrium: Equilibrium,
+    ) -> Vec<Arc<Transaction>> {
+        let mut transactions = vec![];
+        let mut pending_shares = equilibrium.volume;
+
+        while pending_shares > 0 && self.crosses_at(equilibrium.price) {
+            // Failed sell orders are cancelled, the next ones are tried
+            let Ok(transaction) =
+                self.execute_top(equilibrium.price, pending_shares)
+            else {
+                continue;
+            };
+
+            pending_shares -= transaction.traded_shares();
+            transactions.push(transaction);
+        }
+
+        transactions
+    }
+
+    /// Whether the best buy and sell orders both accept `price`.
+    fn crosses_at(&self, price: f32) -> bool {
+        self.buy_orders
+            .peek()
+            .is_some_and(|order| *order.price() >= price)
+            && self
+                .sell_orders
+                .peek()
+                .is_some_and(|order| *order.0.price() <= price)
+    }
+
+    /// Orders crossing `price` from the other side that may trade with an
+    /// order of `investor_id`, leaving out all-or-none orders and the orders
+    /// self-trade prevention would cancel.
+    fn counterparties(
+        &self,
+        side: Side,
+        price: f32,
+        investor_id: &str,
+    ) -> Vec<Counterparty> {
+        let counterparty =
+            |order_price: f32,
+             investor: &Investor,
+             instructions: &OrderInstructions| {
+                let crosses = match side {
+                    Side::Buy => order_price <= price,
+                    Side::Sell => order_price >= price,
+                };
+
+                crosses
+                    && !instructions.is_all_or_none()
+                    && (self.self_trade_prevention.is_none()
+                        || investor.id() != investor_id)
+            };
+
+        match side {
+            Side::Buy => self
+                .sell_orders
+                .iter()
+                .map(|order| &order.0)
+                .filter(|order| {
+                    counterparty(
+                        *order.price(),
+                        order.investor(),
+                        order.instructions(),
+                    )
+                })
+                .map(Counterparty::new)
+                .collect(),
+            Side::Sell => self
+                .buy_orders
+                .iter()
+                .filter(|order| {
+                    counterparty(
+                        *order.price(),
+                        order.investor(),
+                        order.instructions(),
+                    )
+                })
+                .map(Counterparty::new)
+                .collect(),
+        }
+    }
+
+    /// Shows the next peak of iceberg orders whose peak was filled, behind the
+    /// orders already resting at their price. Only the best orders trade, so
+    /// only they are checked.
+    fn replenish_icebergs(&mut self) {
+        if self
+            .buy_orders
+            .peek()
+            .is_some_and(|order| *order.pending_shares() == 0)
+        {
+            let mut order = self.buy_orders.pop().unwrap();
+
+            order.replenish();
+            self.events.push(Self::added_event(&order));
+            self.buy_orders.push(order);
+        }
+
+        if self
+            .sell_orders
+            .peek()
+            .is_some_and(|order| *order.0.pending_shares() == 0)
+        {
+            let Reverse(mut order) = self.sell_orders.pop().unwrap();
+
+            order.replenish();
+            self.events.push(Self::added_event(&order));
+            self.sell_orders.push(Reverse(order));
+        }
+    }
+
+    /// Trades the best buy and sell orders against each other, up to
+    /// `max_shares`. The best sell order is cancelled when its investor
+    /// cannot deliver the shares, leaving the rest of the book untouched.
+    fn execute_top(
+        &mut self,
+        traded_price: f32,
+        max_shares: u32,
+    ) -> Result<Arc<Transaction>, OrderBookError> {
+        let Some(mut sell_order) = self.sell_orders.peek_mut() else {
+            return Err(OrderBookError::NoMatchingOrderAvailable);
+        };
+
+        let Some(mut buy_order) = self.buy_orders.peek_mut() else {
+            return Err(OrderBookError::NoMatchingOrderAvailable);
+        };
+
+        let common_shares_count = cmp::min(
+            *sell_order.0.pending_shares(),
+            *buy_order.pending_shares(),
+        )
+        .min(max_shares);
+
+        let sell_event = Self::executed_event(
+            &sell_order.0,
+            traded_price,
+            common_shares_count,
+        );
+        let buy_event =
+            Self::executed_event(&buy_order, traded_price, common_shares_count);
+
+        let sell_order = match sell_order.0.sell(common_shares_count) {
+            Ok(OrderTransition::Closed(_)) => {
+                drop(sell_order);
+
+                self.sell_orders.pop().unwrap().0.check_order()
+            }
+            Ok(other) => {
+                drop(sell_order);
+
+                other
+            }
+            Err(err) => {
+                drop(sell_order);
+                drop(buy_order);
+
+                let Reverse(order) = self.sell_orders.pop().unwrap();
+                self.events.push(OrderEvent::Deleted {
+                    order_id: order.id().to_owned(),
+                    side: Side::Sell,
+                    reason: CancelReason::Rejected,
+                });
+                self.refresh_market_data();
+
+                return Err(err.into());
+            }
+        };
+
+        let buy_order = match buy_order.buy(common_shares_count)? {
+            OrderTransition::Closed(_) => {
+                drop(buy_order);
+
+                self.buy_orders.pop().unwrap().check_order()
+            }
+            other => {
+                drop(buy_order);
+
+                other
+            }
+        };
+
+        self.events.push(sell_event);
+        self.events.push(buy_event);
+
+        self.replenish_icebergs();
+
+        let transaction = Arc::new(
+            Transaction::new(
+                buy_order,
+                sell_order,
+                common_shares_count,
+                traded_price,
+            )
+            .with_traded_at(self.session.now()),
+        );
+
+        self.ticker.record_trade(&transaction);
+        self.ticker_changed = true;
+
+        for order in self.stop_orders.iter_mut() {
+            order.track_price(traded_price);
+        }
+        self.refresh_market_data();
+
+        self.transactions.record(transaction.clone());
+
+        Ok(transaction.clone())
+    }
+
+    fn apply_transition(
+        &mut self,
+        transition: Option<(SessionPhase, SessionPhase)>,
+    ) -> Result<Vec<Arc<Transaction>>, OrderBookError> {
+        let Some((from, to)) = transition else {
+            return Ok(vec![]);
+        };
+
+        self.market_events.push(MarketEvent::PhaseChanged {
+            asset_id: self.asset_id.to_owned(),
+            from,
+            to,
+        });
+
+        match to {
+            SessionPhase::OpeningAuction
+            | SessionPhase::ClosingAuction
+            | SessionPhase::VolatilityAuction => {
+                self.start_auction();
+                Ok(vec![])
+            }
+            // Halts freeze the book as it is, including a running auction
+            SessionPhase::Halted | SessionPhase::PreOpen => Ok(vec![]),
+            SessionPhase::Continuous | SessionPhase::Closed => {
+                match self.mode {
+                    MatchingMode::Auction => self.uncross(),
+                    MatchingMode::Continuous => Ok(vec![]),
+                }
+            }
+        }
+    }
+
+    fn refresh_market_data(&mut self) {
+        if self.mode == MatchingMode::Auction {
+            let indicative = self.indicative_equilibrium();
+
+            if indicative != self.indicative {
+                self.indicative = indicative;
+                self.market_events.push(MarketEvent::IndicativeUncross {
+                    asset_id: self.asset_id.to_owned(),
+                    equilibrium: indicative,
+                });
+            }
+        }
+
+        let bid = self.buy_orders.peek().map(|best| Quote {
+            price: *best.price(),
+            shares: self
+                .buy_orders
+                .iter()
+                .filter(|order| order.price() == best.price())
+                .map(|order| *order.pending_shares())
+                .sum(),
+        });
+
+        let ask = self.sell_orders.peek().map(|best| Quote {
+            price: *best.0.price(),
+            shares: self
+                .sell_orders
+                .iter()
+                .filter(|order| order.0.price() == best.0.price())
+                .map(|order| *order.0.pending_shares())
+                .sum(),
+        });
+
+        self.ticker_changed |= self.ticker.update_quote(bid, ask);
+    }
+
+    fn reduce_order<T: OrderType>(
+        orders: &mut [Order<T, Open>],
+        order_id: &str,
+        share_count: u32,
+    ) -> Result<OrderEvent, OrderBookError> {
+        let Some(order) =
+            orders.iter_mut().find(|order| order.id() == order_id)
+        else {
+            return Err(OrderBookError::OrderNotFound);
+        };
+
+        order.reduce(share_count)?;
+
+        Ok(OrderEvent::Reduced {
+            order_id: order_id.into(),
+            side: T::SIDE,
+            shares: share_count,
+            remaining: *order.pending_shares(),
+            reason: CancelReason::Requested,
+        })
+    }
+
+    fn added_event<T: OrderType>(order: &Order<T, Open>) -> OrderEvent {
+        OrderEvent::Added {
+            order_id: order.id().into(),
+            side: T::SIDE,
+            price: *order.price(),
+            shares: *order.pending_shares(),
+        }
+    }
+
+    fn executed_event<T: OrderType>(
+        order: &Order<T, Open>,
+        price: f32,
+        shares: u32,
+    ) -> OrderEvent {
+        OrderEvent::Executed {
+            order_id: order.id().into(),
+            side: T::SIDE,
+            price,
+            shares,
+            remaining: *order.pending_shares() - shares,
+        }
+    }
+
+    /// Prices a pegged order from the best lit prices, rejecting it when they
+    /// are not available.
+    fn apply_peg<T: OrderType>(
+        &self,
+        mut order: Order<T, Open>,
+    ) -> Result<Order<T, Open>, OrderBookError> {
+        let Some(peg) = order.instructions().peg() else {
+            return Ok(order);
+        };
+
+        if order.instructions().is_market() {
+            return Ok(order);
+        }
+
+        let (bid, ask) = self.lit_quote();
+        let price = peg
+            .price(T::SIDE, bid, ask)
+            .ok_or(OrderBookError::NoPegReference)?;
+
+        order.reprice(price);
+
+        Ok(order)
+    }
+
+    /// Best prices of the orders resting at a limit price.
+    fn lit_quote(&self) -> (Option<f32>, Option<f32>) {
+        let is_lit = |instructions: &OrderInstructions| {
+            !instructions.is_market() && instructions.peg().is_none()
+        };
+
+        let bid = self
+            .buy_orders
+            .iter()
+            .filter(|order| is_lit(order.instructions()))
+            .map(|order| ComparableFloat(*order.price()))
+            .max();
+        let ask = self
+            .sell_orders
+            .iter()
+            .filter(|order| is_lit(order.0.instructions()))
+            .map(|order| ComparableFloat(*order.0.price()))
+            .min();
+
+        (bid.map(|price| *price), ask.map(|price| *price))
+    }
+
+    /// Moves the pegged orders to the price of their peg after the best lit
+    /// prices changed, keeping their price when the reference is gone.
+    /// Returns whether any order was moved.
+    fn repeg(&mut self) -> bool {
+        let is_pegged = |instructions: &OrderInstructions| {
+            instructions.peg().is_some() && !instructions.is_market()
+        };
+
+        if self.mode == MatchingMode::Auction
+            || !(self
+                .buy_orders
+                .iter()
+                .any(|order| is_pegged(order.instructions()))
+                || self
+                    .sell_orders
+                    .iter()
+                    .any(|order| is_pegged(order.0.instructions())))
+        {
+            return false;
+        }
+
+        let (bid, ask) = self.lit_quote();
+
+        let mut buy_orders = std::mem::take(&mut self.buy_orders).into_vec();
+        let mut sell_orders = std::mem::take(&mut self.sell_orders)
+            .into_vec()
+            .into_iter()
+            .map(|order| order.0)
+            .collect::<Vec<_>>();
+
+        let buy_repegged =
+            Self::repeg_orders(&mut self.events, &mut buy_orders, bid, ask);
+        let sell_repegged =
+            Self::repeg_orders(&mut self.events, &mut sell_orders, bid, ask);
+
+        self.buy_orders = buy_orders.into();
+        self.sell_orders = sell_orders.into_iter().map(Reverse).collect();
+
+        buy_repegged || sell_repegged
+    }
+
+    /// Orders are moved oldest first, so they keep their priority among them.
+    fn repeg_orders<T: OrderType>(
+        events: &mut Vec<OrderEvent>,
+        orders: &mut [Order<T, Open>],
+        bid: Option<f32>,
+        ask: Option<f32>,
+    ) -> bool {
+        let mut repegged = false;
+
+        orders.sort_by_key(|order| order.sequence());
+
+        for order in orders.iter_mut() {
+            if order.instructions().is_market() {
+                continue;
+            }
+
+            let Some(price) = order
+                .instructions()
+                .peg()
+                .and_then(|peg| peg.price(T::SIDE, bid, ask))
+            else {
+                continue;
+            };
+
+            if price == *order.price() {
+                continue;
+            }
+
+            order.repeg(price);
+            events.push(OrderEvent::Replaced {
+                order_id: order.id().into(),
+                side: T::SIDE,
+                price,
+                shares: *order.pending_shares(),
+            });
+            repegged = true;
+        }
+
+        repegged
+    }
+
+    /// Rejects post-only orders that would trade on arrival, or moves them one
+    /// tick away from the best opposite price. Auctions never trade on arrival.
+    /// Repriced orders are validated again against the price band and the
+    /// reference data.
+    fn apply_post_only<T: OrderType>(
+        &self,
+        mut order: Order<T, Open>,
+    ) -> Result<Order<T, Open>, OrderBookError> {
+        let Some(post_only) = order.instructions().post_only() else {
+            return Ok(order);
+        };
+
+        if order.instructions().is_market() {
+            return Err(OrderBookError::PostOnlyWouldCross);
+        }
+
+        if self.mode == MatchingMode::Auction {
+            return Ok(order);
+        }
+
+        let best_price = match T::SIDE {
+            Side::Buy => self.sell_orders.peek().map(|order| *order.0.price()),
+            Side::Sell => self.buy_orders.peek().map(|order| *order.price()),
+        };
+
+        let Some(best_price) = best_price else {
+            return Ok(order);
+        };
+
+        let (crosses, tick_offset) = match T::SIDE {
+            Side::Buy => (*order.price() >= best_price, -1.0),
+            Side::Sell => (*order.price() <= best_price, 1.0),
+        };
+
+        match (crosses, post_only) {
+            (false, _) => Ok(order),
+            (true, PostOnly::Reject) => Err(OrderBookError::PostOnlyWouldCross),
+            (true, PostOnly::Reprice) => {
+                let tick_size = self.reference_data.tick_size(best_price);
+                let ticks = (best_price / tick_size).round() + tick_offset;
+
+                order.reprice(ticks * tick_size);
+
+                self.check_is_order_valid(OrderTransition::Open(order))
+            }
+        }
+    }
+
+    fn check_is_order_valid<T: OrderType>(
+        &self,
+        order: OrderTransition<T>,
+    ) -> Result<Order<T, Open>, OrderBookError> {
+        match order {
+            OrderTransition::Open(order) => {
+                if *order.asset().id() != self.asset_id {
+                    return Err(OrderBookError::InvalidOrderAssetId);
+                }
+
+                let shares = *order.pending_shares() + order.reserve_shares();
+
+                if let Some(stop_price) = order.instructions().stop_price() {
+                    self.reference_data.validate(stop_price, shares)?;
+                }
+
+                // Pegged orders are priced once they reach the book
+                if order.instructions().is_market()
+                    || order.instructions().peg().is_some()
+                {
+                    self.reference_data.validate_shares(shares)?;
+                    return Ok(order);
+                }
+
+                self.reference_data.validate(*order.price(), shares)?;
+
+                // Each peak of an iceberg order must be tradable on its own
+                if order.reserve_shares() > 0 {
+                    self.reference_data
+                        .validate_shares(*order.pending_shares())?;
+                }
+
+                let reference_price = self.reference_price();
+
+                if self
+                    .price_bands
+                    .breaches_static(*order.price(), reference_price)
+                {
+                    let (low, high) = reference_price
+                        .and_then(|price| self.price_bands.static_limits(price))
+                        .unwrap_or_default();
+
+                    return Err(OrderBookError::PriceOutOfBand { low, high });
+                }
+
+                Ok(order)
+            }
+            OrderTransition::Closed(_) => {
+                return Err(OrderBookError::InvalidOrderState);
+            }
+        }
+    }
+}
+
+/// Merges consecutive entries sharing the same price into a single level.
+pub(crate) fn aggregate_levels(levels: Vec<(f32, u32)>) -> Vec<(f32, u32)> {
+    let mut aggregated: Vec<(f32, u32)> = Vec::with_capacity(levels.len());
+
+    for (price, shares) in levels {
+        match aggregated.last_mut() {
+            Some(level) if level.0 == price => level.1 += shares,
+            _ => aggregated.push((price, shares)),
+        }
+    }
+
+    aggregated
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::entities::{asset::Asset, investor::Investor, order::OrderItem};
+
+    use super::*;
+
+    #[test]
+    fn append_orders() {
+        let asset_1 = Asset::new("HGLG11".into());
+        let asset_2 = Asset::new("WRONG11".into());
+
+        let mut book = OrderBook::new(asset_1.id().to_owned());
+
+        let investor = Investor::new(
+            "123".into(),
+            "Foo".into(),
+            vec![(asset_1.id().to_owned(), 10)],
+        );
+
+        let sell_order = Order::<Sell, Open>::new(
+            asset_1.to_owned(),
+            investor.to_owned(),
+            "A".into(),
+            5.0,
+            10,
+        );
+
+        let sell_order_2 = Order::<Sell, Open>::new(
+            asset_1.to_owned(),
+            investor.to_owned(),
+            "A".into(),
+            3.0,
+            10,
+        );
+
+        let buy_order = Order::<Buy, Open>::new(
+            asset_1.to_owned(),
+            investor.to_owned(),
+            "B".into(),
+            3.0,
+            10,
+        );
+
+        let buy_order_2 = Order::<Buy, Open>::new(
+            asset_1.to_owned(),
+            investor.to_owned(),
+            "C".into(),
+            5.0,
+            10,
+        );
+
+        let wrong_order = Order::<Buy, Open>::new(
+            asset_2.to_owned(),
+            investor.to_owned(),
+            "D".into(),
+            1.0,
+            10,
+        );
+
+        assert!(book.append(sell_order.resolve_type()).is_ok());
+        assert!(book.append(sell_order_2.resolve_type()).is_ok());
+        assert!(book.append(buy_order.resolve_type()).is_ok());
+        assert!(book.append(buy_order_2.resolve_type()).is_ok());
+
+        assert_eq!(
+            OrderBookError::InvalidOrderAssetId,
+            book.append(wrong_order.resolve_type()).unwrap_err()
+        );
+
+        assert_eq!(2, book.sell_orders.len());
+        assert_eq!(2, book.buy_orders.len());
+
+        // Buy orders with increased prices should have higher priority
+        assert_eq!(&buy_order_2, book.buy_orders.peek().unwrap());
+
+        // Sell orders with lowest prices should have higher priority
+        assert_eq!(&Reverse(sell_order_2), book.sell_orders.peek().unwrap());
+    }
+
+    #[test]
+    fn match_orders() {
+        const ORDER_PRICE: f32 = 5.0;
+        const INCREASED_ORDER_PRICE: f32 = ORDER_PRICE + 0.5;
+
+        const ORDER_QUANTITY: u32 = 10;
+        const PARTIAL_QUANTITY: u32 = ORDER_QUANTITY / 2;
+
+        let asset = Asset::new("HGLG11".into());
+        let mut book = OrderBook::new(asset.id().to_owned());
+
+        let investor_a = Investor::new(
+            "123".into(),
+            "Foo".into(),
+            vec![(asset.id().to_owned(), 10)],
+        );
+
+        let investor_b = Investor::new("321".into(), "Bar".into(), vec![]);
+
+        let mut order_a = Order::<Sell, Open>::new(
+            asset.to_owned(),
+            investor_a,
+            "A".into(),
+            ORDER_PRICE,
+            ORDER_QUANTITY,
+        );
+
+        let mut order_b = Order::<Buy, Open>::new(
+            asset.to_owned(),
+            investor_b.to_owned(),
+            "B".into(),
+            ORDER_PRICE,
+            PARTIAL_QUANTITY,
+        );
+
+        let mut order_c = Order::<Buy, Open>::new(
+            asset.to_owned(),
+            investor_b.to_owned(),
+            "C".into(),
+            INCREASED_ORDER_PRICE,
+            PARTIAL_QUANTITY,
+        );
+
+        assert!(book.append(order_a.resolve_type()).is_ok());
+        assert!(book.append(order_b.resolve_type()).is_ok());
+        assert!(book.append(order_c.resolve_type()).is_ok());
+
+        assert_eq!(1, book.sell_orders.len());
+        assert_eq!(2, book.buy_orders.len());
+
+        // Buy orders with increased prices should have higher priority
+        assert_eq!(&order_c, book.buy_orders.peek().unwrap());
+
+        /* 1º PARTIAL TRANSACTION */
+
+        let partial_transaction = book.try_match().unwrap();
+
+        const INCREASED_PARTIAL_TOTAL: f32 =
+            INCREASED_ORDER_PRICE * PARTIAL_QUANTITY as f32;
+
+        assert_eq!(PARTIAL_QUANTITY, partial_transaction.traded_shares());
+        assert_eq!(INCREASED_PARTIAL_TOTAL, partial_transaction.total());
+
+        assert!(order_a.sell(PARTIAL_QUANTITY).is_ok());
+        assert!(order_c.buy(PARTIAL_QUANTITY).is_ok());
+
+        assert_eq!(
+            &OrderTransition::Open(order_a.copy()),
+            partial_transaction.selling_order()
+        );
+        assert_eq!(
+            &OrderTransition::Closed(order_c.copy()),
+            partial_transaction.buying_order()
+        );
+
+        assert_eq!(1, book.transactions.len());
+        assert_eq!(1, book.sell_orders.len());
+        assert_eq!(1, book.buy_orders.len());
+        assert_eq!(&order_b, book.buy_orders.peek().unwrap());
+
+        /* 2º PARTIAL TRANSACTION */
+
+        let partial_transaction = book.try_match().unwrap();
+        const PARTIAL_TOTAL: f32 = ORDER_PRICE * PARTIAL_QUANTITY as f32;
+
+        assert_eq!(PARTIAL_QUANTITY, partial_transaction.traded_shares());
+        assert_eq!(PARTIAL_TOTAL, partial_transaction.total());
+
+        assert!(order_a.sell(PARTIAL_QUANTITY).is_ok());
+        assert!(order_b.buy(PARTIAL_QUANTITY).is_ok());
+
+        assert_eq!(
+            &OrderTransition::Closed(order_a.copy()),
+            partial_transaction.selling_order()
+        );
+        assert_eq!(
+            &OrderTransition::Closed(order_b.copy()),
+            partial_transaction.buying_order()
+        );
+
+        assert_eq!(2, book.transactions.len());
+        assert_eq!(0, book.sell_orders.len());
+        assert_eq!(0, book.buy_orders.len());
+
+        assert_eq!(
+            OrderBookError::NoMatchingOrderAvailable,
+            book.try_match().unwrap_err()
+        )
+    }
+
+    #[test]
+    fn update_ticker() {
+        let asset = Asset::new("HGLG11");
+        let mut book = OrderBook::new(asset.id().to_owned());
+
+        let seller = Investor::new("123", "Foo", vec![("HGLG11".into(), 20)]);
+        let buyer = Investor::new("321", "Bar", vec![]);
+
+        assert_eq!(None, book.take_ticker_update());
+
+        for (id, price, shares) in
+            [("A", 5.0, 10), ("B", 5.0, 5), ("C", 6.0, 5)]
+        {
+            let order = Order::<Sell, Open>::new(
+                asset.to_owned(),
+                seller.to_owned(),
+                id,
+                price,
+                shares,
+            );
+
+            book.append(order.resolve_type()).unwrap();
+        }
+
+        let ticker = book.take_ticker_update().unwrap();
+        assert_eq!(
+            Some(&Quote {
+                price: 5.0,
+                shares: 15
+            }),
+            ticker.ask()
+        );
+        assert_eq!(None, ticker.bid());
+
+        let order = Order::<Buy, Open>::new(
+            asset.to_owned(),
+            buyer.to_owned(),
+            "D",
+            4.0,
+            5,
+        );
+        book.append(order.resolve_type()).unwrap();
+        assert!(book.take_ticker_update().is_some());
+
+        // "Orders behind the best price should not publish the ticker"
+        let order =
+            Order::<Buy, Open>::new(asset.to_owned(), buyer, "E", 3.0, 5);
+        book.append(order.resolve_type()).unwrap();
+        assert_eq!(None, book.take_ticker_update());
+
+        let order = Order::<Buy, Open>::new(
+            asset.to_owned(),
+            Investor::new("456", "Baz", vec![]),
+            "F",
+            5.0,
+            12,
+        );
+        book.append(order.resolve_type()).unwrap();
+        book.try_match().unwrap();
+        book.try_match().unwrap();
+
+        let ticker = book.take_ticker_update().unwrap();
+        assert_eq!(Some(5.0), ticker.last_price());
+        assert_eq!(12, ticker.volume());
+        assert_eq!(2, ticker.trade_count());
+        assert_eq!(
+            Some(&Quote {
+                price: 5.0,
+                shares: 3
+            }),
+            ticker.ask()
+        );
+        assert_eq!(
+            Some(&Quote {
+                price: 4.0,
+                shares: 5
+            }),
+            ticker.bid()
+        );
+
+        book.cancel("D").unwrap();
+        assert_eq!(3.0, book.ticker().bid().unwrap().price);
+    }
+
+    #[test]
+    fn call_auction() {
+        let asset = Asset::new("HGLG11");
+        let mut book = OrderBook::new(asset.id().to_owned());
+
+        let seller = Investor::new("123", "Foo", vec![("HGLG11".into(), 300)]);
+        let buyer = Investor::new("321", "Bar", vec![]);
+
+        book.start_auction();
+
+        for (id, price, shares) in [("A", 9.9, 100), ("B", 10.0, 200)] {
+            let order = Order::<Sell, Open>::new(
+                asset.to_owned(),
+                seller.to_owned(),
+                id,
+                price,
+                shares,
+            );
+
+            book.append(order.resolve_type()).unwrap();
+        }
+
+        for (id, price, shares) in [("C", 10.2, 150), ("D", 10.0, 250)] {
+            let order = Order::<Buy, Open>::new(
+                asset.to_owned(),
+                buyer.to_owned(),
+                id,
+                price,
+                shares,
+            );
+
+            book.append(order.resolve_type()).unwrap();
+        }
+
+        // "Crossing orders should rest while the auction is running"
+        assert_eq!(
+            OrderBookError::AuctionInProgress,
+            book.try_match().unwrap_err()
+        );
+
+        let expected = Equilibrium {
+            price: 10.0,
+            volume: 300,
+            imbalance: 100,
+        };
+
+        let market_events = book.drain_market_events();
+        assert_eq!(
+            MarketEvent::AuctionStarted {
+                asset_id: "HGLG11".into()
+            },
+            market_events[0]
+        );
+        assert_eq!(
+            &MarketEvent::IndicativeUncross {
+                asset_id: "HGLG11".into(),
+                equilibrium: Some(expected),
+            },
+            market_events.last().unwrap()
+        );
+
+        let transactions = book.uncross().unwrap();
+
+        assert_eq!(
+            300,
+            transactions
+                .iter()
+                .map(|transaction| transaction.traded_shares())
+                .sum::<u32>()
+        );
+        assert!(transactions
+            .iter()
+            .all(|transaction| transaction.price() == 10.0));
+
+        assert_eq!(MatchingMode::Continuous, book.mode());
+        assert_eq!(
+            vec![MarketEvent::AuctionUncrossed {
+                asset_id: "HGLG11".into(),
+                equilibrium: Some(expected),
+            }],
+            book.drain_market_events()
+        );
+        assert_eq!(vec![(10.0, 100)], book.depth(Side::Buy));
+        assert!(book.depth(Side::Sell).is_empty());
+        assert_eq!(
+            OrderBookError::AuctionNotStarted,
+            book.uncross().unwrap_err()
+        );
+    }
+
+    #[test]
+    fn uncross_without_constrained_orders() {
+        let asset = Asset::new("HGLG11");
+        let mut book = OrderBook::new(asset.id().to_owned());
+
+        let seller = Investor::new("123", "Foo", vec![("HGLG11".into(), 100)]);
+        let buyer = Investor::new("321", "Bar", vec![]);
+
+        book.start_auction();
+
+        book.append(
+            Order::<Sell, Open>::new(
+                asset.to_owned(),
+                seller.to_owned(),
+                "A",
+                9.9,
+                100,
+            )
+            .resolve_type(),
+        )
+        .unwrap();
+
+        for (id, shares, instructions) in [
+            ("B", 150, OrderInstructions::new().with_all_or_none()),
+            ("C", 80, OrderInstructions::new().with_min_quantity(80)),
+            ("D", 50, OrderInstructions::new()),
+        ] {
+            let order = Order::<Buy, Open>::new(
+                asset.to_owned(),
+                buyer.to_owned(),
+                id,
+                10.0,
+                shares,
+            )
+            .with_instructions(instructions);
+
+            book.append(order.resolve_type()).unwrap();
+        }
+
+        assert_eq!(50, book.indicative_equilibrium().unwrap().volume);
+
+        // "Constrained orders should not be partially filled at the uncross"
+        let transactions = book.uncross().unwrap();
+
+        assert_eq!(1, transactions.len());
+        assert_eq!("D", transactions[0].buying_order().get_order_id());
+        assert_eq!(50, transactions[0].traded_shares());
+        assert_eq!(vec![(10.0, 230)], book.depth(Side::Buy));
+        assert_eq!(vec![(9.9, 50)], book.depth(Side::Sell));
+    }
+
+    #[test]
+    fn cancel_undeliverable_orders_at_uncross() {
+        let asset = Asset::new("HGLG11");
+        let mut book = OrderBook::new(asset.id().to_owned());
+
+        let buyer = Investor::new("321", "Bar", vec![]);
+
+        book.start_auction();
+
+        for (id, price, shares, holdings) in
+            [("A", 9.9, 60, 60), ("B", 10.0, 100, 0), ("C", 10.0, 40, 40)]
+        {
+            let seller = Investor::new(
+                id,
+                "Foo",
+                vec![(asset.id().to_owned(), holdings)],
+            );
+            let order = Order::<Sell, Open>::new(
+                asset.to_owned(),
+                seller,
+                id,
+                price,
+                shares,
+            );
+
+            book.append(order.resolve_type()).unwrap();
+        }
+
+        book.append(
+            Order::<Buy, Open>::new(asset.to_owned(), buyer, "D", 10.0, 150)
+                .resolve_type(),
+        )
+        .unwrap();
+        book.drain_events();
+
+        assert_eq!(150, book.indicative_equilibrium().unwrap().volume);
+
+        // "Trades before a failed execution should be kept"
+        let transactions = book.uncross().unwrap();
+
+        assert_eq!(2, transactions.len());
+        assert_eq!("A", transactions[0].selling_order().get_order_id());
+        assert_eq!("C", transactions[1].selling_order().get_order_id());
+        assert_eq!(MatchingMode::Continuous, book.mode());
+        assert_eq!(vec![(10.0, 50)], book.depth(Side::Buy));
+        assert!(book.depth(Side::Sell).is_empty());
+        assert!(book.drain_events().contains(&OrderEvent::Deleted {
+            order_id: "B".into(),
+            side: Side::Sell,
+            reason: CancelReason::Rejected,
+        }));
+    }
+
+    #[test]
+    fn keep_trades_before_failed_executions() {
+        let asset = Asset::new("HGLG11");
+        let mut book = OrderBook::new(asset.id().to_owned());
+
+        for (id, holdings) in [("A", 10), ("B", 0), ("C", 10)] {
+            let seller = Investor::new(
+                id,
+                "Foo",
+                vec![(asset.id().to_owned(), holdings)],
+            );
+            let order =
+                Order::<Sell, Open>::new(asset.to_owned(), seller, id, 5.0, 10);
+
+            book.append(order.resolve_type()).unwrap();
+        }
+        book.drain_events();
+
+        // "Orders behind an undeliverable sell order should still trade"
+        let transactions = book
+            .submit(
+                Order::<Buy, Open>::new(
+                    asset.to_owned(),
+                    Investor::new("321", "Bar", vec![]),
+                    "D",
+                    5.0,
+                    30,
+                )
+                .resolve_type(),
+            )
+            .unwrap();
+
+        assert_eq!(2, transactions.len());
+        assert_eq!("A", transactions[0].selling_order().get_order_id());
+        assert_eq!("C", transactions[1].selling_order().get_order_id());
+        assert_eq!(vec![(5.0, 10)], book.depth(Side::Buy));
+        assert!(book.drain_events().contains(&OrderEvent::Deleted {
+            order_id: "B".into(),
+            side: Side::Sell,
+            reason: CancelReason::Rejected,
+        }));
+    }
+
+    #[test]
+    fn follow_session_phases() {
+        use chrono::{Duration, FixedOffset, NaiveTime, TimeZone, Utc};
+
+        use crate::session::{Clock, ManualClock, SessionCalendar};
+
+        let clock = Arc::new(ManualClock::new(
+            Utc.with_ymd_and_hms(2023, 8, 14, 9, 30, 0).unwrap(),
+        ));
+
+        let calendar = SessionCalendar::new(FixedOffset::east_opt(0).unwrap())
+            .with_phase(
+                NaiveTime::from_hms_opt(9, 30, 0).unwrap(),
+                SessionPhase::PreOpen,
+            )
+            .with_phase(
+                NaiveTime::from_hms_opt(9, 45, 0).unwrap(),
+                SessionPhase::OpeningAuction,
+            )
+            .with_phase(
+                NaiveTime::from_hms_opt(10, 0, 0).unwrap(),
+                SessionPhase::Continuous,
+            );
+
+        let asset = Asset::new("HGLG11");
+        let mut book = OrderBook::new(asset.id().to_owned())
+            .with_session(TradingSession::new(calendar, clock.clone()));
+
+        let seller = Investor::new("123", "Foo", vec![("HGLG11".into(), 10)]);
+        let buyer = Investor::new("321", "Bar", vec![]);
+
+        let sell_order = Order::<Sell, Open>::new(
+            asset.to_owned(),
+            seller.to_owned(),
+            "A",
+            5.0,
+            10,
+        );
+        let buy_order =
+            Order::<Buy, Open>::new(asset.to_owned(), buyer, "B", 5.5, 10);
+
+        assert_eq!(
+            OrderBookError::PhaseNotAcceptingOrders(SessionPhase::PreOpen),
+            book.append(sell_order.resolve_type()).unwrap_err()
+        );
+
+        clock.advance(Duration::minutes(15));
+        assert!(book.update_session().unwrap().is_empty());
+        assert_eq!(MatchingMode::Auction, book.mode());
+
+        book.append(sell_order.resolve_type()).unwrap();
+        book.append(buy_order.resolve_type()).unwrap();
+
+        clock.advance(Duration::minutes(15));
+        let transactions = book.update_session().unwrap();
+
+        assert_eq!(1, transactions.len());
+        assert_eq!(clock.now(), transactions[0].traded_at());
+        assert_eq!(SessionPhase::Continuous, book.phase());
+        assert_eq!(MatchingMode::Continuous, book.mode());
+
+        let phases = book
+            .drain_market_events()
+            .into_iter()
+            .filter_map(|event| match event {
+                MarketEvent::PhaseChanged { from, to, .. } => Some((from, to)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![
+                (SessionPhase::PreOpen, SessionPhase::OpeningAuction),
+                (SessionPhase::OpeningAuction, SessionPhase::Continuous),
+            ],
+            phases
+        );
+
+        // "Halted books should reject new orders until resumed"
+        book.halt(Halt::new()).unwrap();
+        let order = Order::<Sell, Open>::new(asset, seller, "C", 5.0, 10);
+        assert_eq!(
+            OrderBookError::PhaseNotAcceptingOrders(SessionPhase::Halted),
+            book.append(order.resolve_type()).unwrap_err()
+        );
+
+        book.resume().unwrap();
+        assert!(book.append(order.resolve_type()).is_ok());
+    }
+
+    #[test]
+    fn prevent_self_trades() {
+        let asset = Asset::new("HGLG11");
+        let investor =
+            Investor::new("123", "Foo", vec![(asset.id().to_owned(), 100)]);
+        let seller =
+            Investor::new("321", "Bar", vec![(asset.id().to_owned(), 100)]);
+
+        // The investor rests a sell order, then crosses it with a buy order
+        // priced to also match the next seller
+        let book = |mode, buy_shares| {
+            let mut book = OrderBook::new(asset.id().to_owned())
+                .with_self_trade_prevention(mode);
+
+            let orders = [
+                Order::<Sell, Open>::new(
+                    asset.to_owned(),
+                    investor.to_owned(),
+                    "A",
+                    5.0,
+                    10,
+                )
+                .resolve_type(),
+                Order::<Sell, Open>::new(
+                    asset.to_owned(),
+                    seller.to_owned(),
+                    "B",
+                    5.1,
+                    10,
+                )
+                .resolve_type(),
+                Order::<Buy, Open>::new(
+                    asset.to_owned(),
+                    investor.to_owned(),
+                    "C",
+                    5.2,
+                    buy_shares,
+                )
+                .resolve_type(),
+            ];
+
+            for order in orders {
+                book.append(order).unwrap();
+            }
+
+            book.drain_events();
+            book
+        };
+
+        let deleted = |order_id: &str, side| OrderEvent::Deleted {
+            order_id: order_id.into(),
+            side,
+            reason: CancelReason::SelfTradePrevention,
+        };
+
+        let mut newest = book(SelfTradePrevention::CancelNewest, 10);
+        assert_eq!(
+            OrderBookError::NoMatchingOrderAvailable,
+            newest.try_match().unwrap_err()
+        );
+        assert_eq!(vec![deleted("C", Side::Buy)], newest.drain_events());
+
+        // "Cancelling the resting order should let the next seller match"
+        let mut oldest = book(SelfTradePrevention::CancelOldest, 10);
+        let transaction = oldest.try_match().unwrap();
+        assert_eq!("B", transaction.selling_order().get_order_id());
+        assert_eq!(deleted("A", Side::Sell), oldest.drain_events()[0]);
+
+        let mut both = book(SelfTradePrevention::CancelBoth, 10);
+        assert!(both.try_match().is_err());
+        assert_eq!(
+            vec![deleted("C", Side::Buy), deleted("A", Side::Sell)],
+            both.drain_events()
+        );
+        assert_eq!(1, both.sell_orders.len());
+
+        let mut decrement = book(SelfTradePrevention::DecrementAndCancel, 4);
+        assert!(decrement.try_match().is_err());
+        assert_eq!(
+            vec![
+                OrderEvent::Reduced {
+                    order_id: "A".into(),
+                    side: Side::Sell,
+                    shares: 4,
+                    remaining: 6,
+                    reason: CancelReason::SelfTradePrevention,
+                },
+                deleted("C", Side::Buy),
+            ],
+            decrement.drain_events()
+        );
+    }
+
+    #[test]
+    fn trigger_stop_orders() {
+        use crate::entities::order::OrderInstructions;
+
+        let asset = Asset::new("HGLG11");
+        let mut book = OrderBook::new(asset.id().to_owned());
+
+        let investor =
+            |id| Investor::new(id, "Foo", vec![(asset.id().to_owned(), 100)]);
+        let buy = |id, price, instructions| {
+            Order::<Buy, Open>::new(
+                asset.to_owned(),
+                investor(id),
+                id,
+                price,
+                10,
+            )
+            .with_instructions(instructions)
+            .resolve_type()
+        };
+        let sell = |id, price, instructions| {
+            Order::<Sell, Open>::new(
+                asset.to_owned(),
+                investor(id),
+                id,
+                price,
+                10,
+            )
+            .with_instructions(instructions)
+            .resolve_type()
+        };
+        let stop_loss = |stop_price| {
+            OrderInstructions::new()
+                .with_market_price()
+                .with_stop_price(stop_price)
+        };
+
+        for order in [
+            buy("B1", 9.8, OrderInstructions::new()),
+            buy("B2", 9.5, OrderInstructions::new()),
+            buy("B3", 9.0, OrderInstructions::new()),
+            sell("S1", 0.0, stop_loss(9.8)),
+            sell("S2", 0.0, stop_loss(9.5)),
+            buy("B4", 11.2, OrderInstructions::new().with_stop_price(11.0)),
+        ] {
+            assert!(book.submit(order).unwrap().is_empty());
+        }
+
+        // "Stop orders should not be displayed on the book"
+        assert_eq!(3, book.stop_orders().len());
+        assert_eq!(3, book.depth(Side::Buy).len());
+        assert!(book.depth(Side::Sell).is_empty());
+
+        // "Each triggered stop should trigger the next one"
+        let transactions = book
+            .submit(sell("S3", 9.8, OrderInstructions::new()))
+            .unwrap();
+
+        let trades = transactions
+            .iter()
+            .map(|transaction| {
+                (
+                    transaction.selling_order().get_order_id(),
+                    transaction.price(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec![("S3", 9.8), ("S1", 9.5), ("S2", 9.0)], trades);
+        assert_eq!(1, book.stop_orders().len());
+        assert!(book.depth(Side::Buy).is_empty());
+
+        // "Unfilled market orders should be cancelled instead of resting"
+        let transactions = book
+            .submit(sell(
+                "S4",
+                0.0,
+                OrderInstructions::new().with_market_price(),
+            ))
+            .unwrap();
+
+        assert!(transactions.is_empty());
+        assert!(book.depth(Side::Sell).is_empty());
+        assert_eq!(
+            Some(&OrderEvent::Deleted {
+                order_id: "S4".into(),
+                side: Side::Sell,
+                reason: CancelReason::Unfilled,
+            }),
+            book.drain_events().last()
+        );
+    }
+
+    #[test]
+    fn trigger_trailing_stops() {
+        use crate::entities::order::OrderInstructions;
+
+        let asset = Asset::new("HGLG11");
+        let mut book = OrderBook::new(asset.id().to_owned());
+
+        let investor =
+            |id| Investor::new(id, "Foo", vec![(asset.id().to_owned(), 100)]);
+        let buy = |id, price, shares| {
+            Order::<Buy, Open>::new(
+                asset.to_owned(),
+                investor(id),
+                id,
+                price,
+                shares,
+            )
+            .resolve_type()
+        };
+        let sell = |id, price, shares| {
+            Order::<Sell, Open>::new(
+                asset.to_owned(),
+                investor(id),
+                id,
+                price,
+                shares,
+            )
+        };
+        let trade = |book: &mut OrderBook, price| {
+            book.submit(buy("B", price, 1)).unwrap();
+            book.submit(sell("S", price, 1).resolve_type()).unwrap()
+        };
+
+        trade(&mut book, 10.0);
+
+        let trailing_stop = sell("T", 0.0, 10).with_instructions(
+            OrderInstructions::new()
+                .with_market_price()
+                .with_trailing_amount(0.5),
+        );
+        book.submit(trailing_stop.resolve_type()).unwrap();
+        assert_eq!(
+            Some(9.5),
+            book.stop_orders()[0].instructions().stop_price()
+        );
+
+        trade(&mut book, 11.0);
+        assert_eq!(
+            Some(10.5),
+            book.stop_orders()[0].instructions().stop_price()
+        );
+
+        book.submit(buy("D", 10.4, 10)).unwrap();
+
+        // "Falling back to the trailing stop price should trigger it"
+        assert_eq!(1, trade(&mut book, 10.6).len());
+        let transactions = trade(&mut book, 10.5);
+
+        assert_eq!(2, transactions.len());
+        assert_eq!("T", transactions[1].selling_order().get_order_id());
+        assert_eq!(10.4, transactions[1].price());
+        assert!(book.stop_orders().is_empty());
+    }
+
+    #[test]
+    fn replenish_iceberg_orders() {
+        use crate::entities::order::OrderInstructions;
+
+        let asset = Asset::new("HGLG11");
+        let mut book = OrderBook::new(asset.id().to_owned());
+
+        let seller =
+            Investor::new("123", "Foo", vec![(asset.id().to_owned(), 100)]);
+        let buyer = Investor::new("321", "Bar", vec![]);
+
+        let iceberg = Order::<Sell, Open>::new(
             asset.to_owned(),
-            investor_a,
-            "A".into(),
-            ORDER_PRICE,
-            ORDER_QUANTITY,
+            seller.to_owned(),
+            "I",
+            5.0,
+            25,
+        )
+        .with_instructions(OrderInstructions::new().with_display_quantity(10));
+        let plain =
+            Order::<Sell, Open>::new(asset.to_owned(), seller, "P", 5.0, 10);
+        let buy = |id| {
+            Order::<Buy, Open>::new(
+                asset.to_owned(),
+                buyer.to_owned(),
+                id,
+                5.0,
+                10,
+            )
+            .resolve_type()
+        };
+
+        book.submit(iceberg.resolve_type()).unwrap();
+        book.submit(plain.resolve_type()).unwrap();
+
+        // "Only the peak should be displayed"
+        assert_eq!(vec![(5.0, 20)], book.depth(Side::Sell));
+        book.drain_events();
+
+        let transactions = book.submit(buy("A")).unwrap();
+        assert_eq!("I", transactions[0].selling_order().get_order_id());
+        assert_eq!(vec![(5.0, 20)], book.depth(Side::Sell));
+
+        let events = book.drain_events();
+        assert_eq!(
+            OrderEvent::Added {
+                order_id: "I".into(),
+                side: Side::Sell,
+                price: 5.0,
+                shares: 10,
+            },
+            events[events.len() - 1]
         );
 
-        let mut order_b = Order::<Buy, Open>::new(
+        // "A replenished peak should lose its time priority"
+        let transactions = book.submit(buy("B")).unwrap();
+        assert_eq!("P", transactions[0].selling_order().get_order_id());
+
+        book.submit(buy("C")).unwrap();
+        assert_eq!(vec![(5.0, 5)], book.depth(Side::Sell));
+    }
+
+    #[test]
+    fn post_only_orders() {
+        use crate::entities::order::OrderInstructions;
+
+        let asset = Asset::new("HGLG11");
+        let mut book = OrderBook::new(asset.id().to_owned());
+
+        let maker = Investor::new("123", "Foo", vec![]);
+        let order = |id, price, post_only| {
+            Order::<Buy, Open>::new(
+                asset.to_owned(),
+                maker.to_owned(),
+                id,
+                price,
+                10,
+            )
+            .with_instructions(
+                OrderInstructions::new().with_post_only(post_only),
+            )
+            .resolve_type()
+        };
+
+        book.submit(
+            Order::<Sell, Open>::new(
+                asset.to_owned(),
+                Investor::new("321", "Bar", vec![]),
+                "S",
+                5.0,
+                10,
+            )
+            .resolve_type(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            OrderBookError::PostOnlyWouldCross,
+            book.submit(order("A", 5.0, PostOnly::Reject)).unwrap_err()
+        );
+
+        assert!(book.submit(order("B", 4.9, PostOnly::Reject)).is_ok());
+
+        // "A crossing order should rest one tick below the best ask"
+        assert!(book
+            .submit(order("C", 5.2, PostOnly::Reprice))
+            .unwrap()
+            .is_empty());
+        assert_eq!(vec![(4.99, 10), (4.9, 10)], book.depth(Side::Buy));
+        assert_eq!(vec![(5.0, 10)], book.depth(Side::Sell));
+    }
+
+    #[test]
+    fn validate_repriced_post_only_orders() {
+        use crate::entities::order::OrderInstructions;
+
+        let asset = Asset::new("HGLG11");
+        let mut book = OrderBook::new(asset.id().to_owned())
+            .with_price_bands(PriceBands::new().with_static_band(50.0));
+
+        book.set_reference_price(10.0);
+
+        book.submit(
+            Order::<Buy, Open>::new(
+                asset.to_owned(),
+                Investor::new("321", "Bar", vec![]),
+                "B",
+                15.0,
+                10,
+            )
+            .resolve_type(),
+        )
+        .unwrap();
+
+        let post_only = Order::<Sell, Open>::new(
             asset.to_owned(),
-            investor_b.to_owned(),
-            "B".into(),
-            ORDER_PRICE,
-            PARTIAL_QUANTITY,
+            Investor::new("123", "Foo", vec![(asset.id().to_owned(), 10)]),
+            "S",
+            14.0,
+            10,
+        )
+        .with_instructions(
+            OrderInstructions::new().with_post_only(PostOnly::Reprice),
         );
 
-        let mut order_c = Order::<Buy, Open>::new(
+        // "One tick above the best bid is outside the static band"
+        assert_eq!(
+            OrderBookError::PriceOutOfBand {
+                low: 5.0,
+                high: 15.0
+            },
+            book.submit(post_only.resolve_type()).unwrap_err()
+        );
+        assert!(book.depth(Side::Sell).is_empty());
+    }
+
+    #[test]
+    fn all_or_none_orders() {
+        use crate::entities::order::OrderInstructions;
+
+        let asset = Asset::new("HGLG11");
+        let mut book = OrderBook::new(asset.id().to_owned());
+
+        let seller =
+            Investor::new("123", "Foo", vec![(asset.id().to_owned(), 100)]);
+        let buyer = Investor::new("321", "Bar", vec![]);
+
+        let sell = |id, shares| {
+            Order::<Sell, Open>::new(
+                asset.to_owned(),
+                seller.to_owned(),
+                id,
+                5.0,
+                shares,
+            )
+            .resolve_type()
+        };
+        let all_or_none = Order::<Buy, Open>::new(
             asset.to_owned(),
-            investor_b.to_owned(),
-            "C".into(),
-            INCREASED_ORDER_PRICE,
-            PARTIAL_QUANTITY,
+            buyer.to_owned(),
+            "AON",
+            5.0,
+            30,
+        )
+        .with_instructions(OrderInstructions::new().with_all_or_none());
+        let buy = Order::<Buy, Open>::new(asset.to_owned(), buyer, "B", 5.0, 5);
+
+        book.submit(sell("S1", 20)).unwrap();
+
+        // "An order that cannot be filled in full should rest"
+        assert!(book.submit(all_or_none.resolve_type()).unwrap().is_empty());
+        assert_eq!(vec![(5.0, 30)], book.depth(Side::Buy));
+
+        // "Orders behind it should still trade"
+        let transactions = book.submit(buy.resolve_type()).unwrap();
+        assert_eq!("B", transactions[0].buying_order().get_order_id());
+        assert_eq!(vec![(5.0, 15)], book.depth(Side::Sell));
+
+        let transactions = book.submit(sell("S2", 20)).unwrap();
+        assert_eq!(2, transactions.len());
+        assert!(transactions
+            .iter()
+            .all(|transaction| transaction.buying_order().get_order_id()
+                == "AON"));
+        assert!(book.depth(Side::Buy).is_empty());
+        assert_eq!(vec![(5.0, 5)], book.depth(Side::Sell));
+    }
+
+    #[test]
+    fn min_quantity_orders() {
+        use crate::entities::order::OrderInstructions;
+
+        let asset = Asset::new("HGLG11");
+        let mut book = OrderBook::new(asset.id().to_owned());
+
+        let seller =
+            Investor::new("123", "Foo", vec![(asset.id().to_owned(), 300)]);
+        let buyer = Investor::new("321", "Bar", vec![]);
+
+        let sell = |id, price, shares, min_quantity: Option<u32>| {
+            let mut instructions = OrderInstructions::new();
+
+            if let Some(min_quantity) = min_quantity {
+                instructions = instructions.with_min_quantity(min_quantity);
+            }
+
+            Order::<Sell, Open>::new(
+                asset.to_owned(),
+                seller.to_owned(),
+                id,
+                price,
+                shares,
+            )
+            .with_instructions(instructions)
+            .resolve_type()
+        };
+        let buy = |id, price, shares, min_quantity: Option<u32>| {
+            let mut instructions = OrderInstructions::new();
+
+            if let Some(min_quantity) = min_quantity {
+                instructions = instructions.with_min_quantity(min_quantity);
+            }
+
+            Order::<Buy, Open>::new(
+                asset.to_owned(),
+                buyer.to_owned(),
+                id,
+                price,
+                shares,
+            )
+            .with_instructions(instructions)
+            .resolve_type()
+        };
+
+        book.submit(sell("S1", 5.0, 10, None)).unwrap();
+        book.submit(sell("S2", 5.1, 50, None)).unwrap();
+        book.submit(sell("S3", 5.2, 30, None)).unwrap();
+
+        // "Counterparties below the minimum should be skipped, and the last
+        // shares filled at once even if fewer than the minimum"
+        let transactions = book.submit(buy("B", 5.2, 80, Some(40))).unwrap();
+        let fills = transactions
+            .iter()
+            .map(|transaction| {
+                (
+                    transaction.selling_order().get_order_id(),
+                    transaction.traded_shares(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(vec![("S2", 50), ("S3", 30)], fills);
+
+        // "Skipped orders should keep their priority"
+        assert_eq!(vec![(5.0, 10)], book.depth(Side::Sell));
+        let transactions = book.submit(buy("C", 5.1, 10, None)).unwrap();
+        assert_eq!("S1", transactions[0].selling_order().get_order_id());
+
+        // "Orders without a large enough counterparty should rest while the
+        // orders behind them trade"
+        book.submit(sell("M", 5.0, 100, Some(50))).unwrap();
+        book.submit(sell("S4", 5.1, 20, None)).unwrap();
+
+        let transactions = book.submit(buy("D", 5.1, 20, None)).unwrap();
+        assert_eq!("S4", transactions[0].selling_order().get_order_id());
+        assert_eq!(vec![(5.0, 100)], book.depth(Side::Sell));
+
+        let transactions = book.submit(buy("E", 5.0, 60, None)).unwrap();
+        assert_eq!("M", transactions[0].selling_order().get_order_id());
+        assert_eq!(vec![(5.0, 40)], book.depth(Side::Sell));
+    }
+
+    #[test]
+    fn peg_orders() {
+        use crate::entities::order::{OrderInstructions, PegReference};
+
+        let asset = Asset::new("HGLG11");
+        let mut book = OrderBook::new(asset.id().to_owned());
+
+        let seller =
+            Investor::new("123", "Foo", vec![(asset.id().to_owned(), 100)]);
+        let buyer = Investor::new("321", "Bar", vec![]);
+
+        let sell = |id, price, peg: Option<PegReference>| {
+            let mut instructions = OrderInstructions::new();
+
+            if let Some(reference) = peg {
+                instructions = instructions.with_peg(reference, 0.0);
+            }
+
+            Order::<Sell, Open>::new(
+                asset.to_owned(),
+                seller.to_owned(),
+                id,
+                price,
+                10,
+            )
+            .with_instructions(instructions)
+            .resolve_type()
+        };
+        let buy = |id, price, peg: Option<PegReference>| {
+            let mut instructions = OrderInstructions::new();
+
+            if let Some(reference) = peg {
+                instructions = instructions.with_peg(reference, 0.0);
+            }
+
+            Order::<Buy, Open>::new(
+                asset.to_owned(),
+                buyer.to_owned(),
+                id,
+                price,
+                10,
+            )
+            .with_instructions(instructions)
+            .resolve_type()
+        };
+
+        assert_eq!(
+            OrderBookError::NoPegReference,
+            book.submit(buy("M", 0.0, Some(PegReference::Midpoint)))
+                .unwrap_err()
         );
 
-        assert!(book.append(order_a.resolve_type()).is_ok());
-        assert!(book.append(order_b.resolve_type()).is_ok());
-        assert!(book.append(order_c.resolve_type()).is_ok());
+        book.submit(buy("B", 5.0, None)).unwrap();
+        book.submit(sell("S1", 6.0, None)).unwrap();
+        book.submit(buy("M", 0.0, Some(PegReference::Midpoint)))
+            .unwrap();
+        book.submit(sell("P", 0.0, Some(PegReference::Primary)))
+            .unwrap();
 
-        assert_eq!(1, book.sell_orders.len());
-        assert_eq!(2, book.buy_orders.len());
+        assert_eq!(vec![(5.5, 10), (5.0, 10)], book.depth(Side::Buy));
+        assert_eq!(vec![(6.0, 20)], book.depth(Side::Sell));
+        book.drain_events();
 
-        // Buy orders with increased prices should have higher priority
-        assert_eq!(&order_c, book.buy_orders.peek().unwrap());
+        // "Pegged orders should follow the best lit prices only"
+        assert!(book.submit(sell("S0", 5.5, None)).unwrap().is_empty());
+        assert_eq!(vec![(5.25, 10), (5.0, 10)], book.depth(Side::Buy));
+        assert_eq!(vec![(5.5, 20), (6.0, 10)], book.depth(Side::Sell));
+        assert!(book.drain_events().contains(&OrderEvent::Replaced {
+            order_id: "M".into(),
+            side: Side::Buy,
+            price: 5.25,
+            shares: 10,
+        }));
 
-        /* 1º PARTIAL TRANSACTION */
+        // "A re-pegged order should rank behind the lit orders already
+        // resting at its new price"
+        let transactions = book.submit(buy("X", 5.5, None)).unwrap();
+        assert_eq!(1, transactions.len());
+        assert_eq!("S0", transactions[0].selling_order().get_order_id());
+        assert_eq!(vec![(5.5, 10), (5.0, 10)], book.depth(Side::Buy));
+        assert_eq!(vec![(6.0, 20)], book.depth(Side::Sell));
 
-        let partial_transaction = book.try_match().unwrap();
+        // "Pegged orders should match each other like lit orders"
+        let transactions = book
+            .submit(sell("Q", 0.0, Some(PegReference::Midpoint)))
+            .unwrap();
+        assert_eq!("M", transactions[0].buying_order().get_order_id());
+        assert_eq!(5.5, transactions[0].price());
+    }
 
-        const INCREASED_PARTIAL_TOTAL: f32 =
-            INCREASED_ORDER_PRICE * PARTIAL_QUANTITY as f32;
+    #[test]
+    fn cross_dark_orders() {
+        use crate::entities::order::OrderInstructions;
 
-        assert_eq!(PARTIAL_QUANTITY, partial_transaction.traded_shares());
-        assert_eq!(INCREASED_PARTIAL_TOTAL, partial_transaction.total());
+        let asset = Asset::new("HGLG11");
+        let mut book = OrderBook::new(asset.id().to_owned()).with_dark_book();
 
-        assert!(order_a.sell(PARTIAL_QUANTITY).is_ok());
-        assert!(order_c.buy(PARTIAL_QUANTITY).is_ok());
+        let seller =
+            Investor::new("123", "Foo", vec![(asset.id().to_owned(), 100)]);
+        let buyer = Investor::new("321", "Bar", vec![]);
+
+        let dark_buy = || {
+            Order::<Buy, Open>::new(
+                asset.to_owned(),
+                buyer.to_owned(),
+                "DB",
+                5.5,
+                10,
+            )
+            .with_instructions(OrderInstructions::new().with_dark())
+            .resolve_type()
+        };
+
+        book.submit(
+            Order::<Buy, Open>::new(
+                asset.to_owned(),
+                buyer.to_owned(),
+                "B",
+                5.0,
+                10,
+            )
+            .resolve_type(),
+        )
+        .unwrap();
+        book.submit(
+            Order::<Sell, Open>::new(
+                asset.to_owned(),
+                seller.to_owned(),
+                "S",
+                6.0,
+                10,
+            )
+            .resolve_type(),
+        )
+        .unwrap();
+        book.drain_events();
 
+        let transactions = book
+            .submit(
+                Order::<Sell, Open>::new(
+                    asset.to_owned(),
+                    seller,
+                    "DS",
+                    5.5,
+                    20,
+                )
+                .with_instructions(OrderInstructions::new().with_dark())
+                .resolve_type(),
+            )
+            .unwrap();
+
+        // "Dark orders should never be displayed"
+        assert!(transactions.is_empty());
+        assert_eq!(vec![(5.0, 10)], book.depth(Side::Buy));
+        assert_eq!(vec![(6.0, 10)], book.depth(Side::Sell));
+        assert!(book.drain_events().is_empty());
+
+        let transactions = book.submit(dark_buy()).unwrap();
+
+        assert_eq!(1, transactions.len());
+        assert_eq!("DB", transactions[0].buying_order().get_order_id());
+        assert_eq!(5.5, transactions[0].price());
+        assert!(transactions[0].is_dark());
+        assert_eq!(None, book.ticker().last_price());
+        assert_eq!(1, book.dark_book().unwrap().len());
+
+        let mut lit_book = OrderBook::new(asset.id().to_owned());
         assert_eq!(
-            &OrderTransition::Open(order_a.copy()),
-            partial_transaction.selling_order()
+            OrderBookError::DarkPoolUnavailable,
+            lit_book.append(dark_buy()).unwrap_err()
+        );
+
+        // "Disabling the dark pool should cancel the orders resting in it"
+        let cancelled = book.update_rules(
+            ReferenceData::new(),
+            PriceBands::new(),
+            None,
+            false,
+            MatchingPolicy::Fifo,
         );
+        assert_eq!(vec![("DS".to_owned(), Side::Sell)], cancelled);
+        assert!(book.dark_book().is_none());
+    }
+
+    #[test]
+    fn keep_pro_rata_trades_before_failed_executions() {
+        let asset = Asset::new("HGLG11");
+        let mut book = OrderBook::new(asset.id().to_owned())
+            .with_matching_policy(MatchingPolicy::ProRata);
+
+        for (id, holdings) in [("A", 10), ("B", 0), ("C", 10)] {
+            let seller = Investor::new(
+                id,
+                "Foo",
+                vec![(asset.id().to_owned(), holdings)],
+            );
+            let order =
+                Order::<Sell, Open>::new(asset.to_owned(), seller, id, 5.0, 10);
+
+            book.append(order.resolve_type()).unwrap();
+        }
+
+        // "Shares allocated before a failed execution should stay traded"
+        let transactions = book
+            .submit(
+                Order::<Buy, Open>::new(
+                    asset.to_owned(),
+                    Investor::new("321", "Bar", vec![]),
+                    "D",
+                    5.0,
+                    30,
+                )
+                .resolve_type(),
+            )
+            .unwrap();
+
+        let sellers = transactions
+            .iter()
+            .map(|transaction| transaction.selling_order().get_order_id())
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["A", "C"], sellers);
+        assert_eq!(vec![(5.0, 10)], book.depth(Side::Buy));
+    }
+
+    #[test]
+    fn match_pro_rata() {
+        let asset = Asset::new("HGLG11");
+
+        let seller =
+            Investor::new("123", "Foo", vec![(asset.id().to_owned(), 300)]);
+        let buyer = Investor::new("321", "Bar", vec![]);
+
+        let sell = |id, price, shares| {
+            Order::<Sell, Open>::new(
+                asset.to_owned(),
+                seller.to_owned(),
+                id,
+                price,
+                shares,
+            )
+            .resolve_type()
+        };
+        let buy = |shares| {
+            Order::<Buy, Open>::new(
+                asset.to_owned(),
+                buyer.to_owned(),
+                "B",
+                5.0,
+                shares,
+            )
+            .resolve_type()
+        };
+
+        for (policy, expected) in [
+            (MatchingPolicy::Fifo, vec![("A", 10), ("B", 23)]),
+            (
+                MatchingPolicy::ProRata,
+                vec![("A", 4), ("B", 10), ("C", 19)],
+            ),
+            (
+                MatchingPolicy::ProRataTopOrder,
+                vec![("A", 10), ("B", 8), ("C", 15)],
+            ),
+        ] {
+            let mut book = OrderBook::new(asset.id().to_owned())
+                .with_matching_policy(policy);
+
+            book.submit(sell("A", 5.0, 10)).unwrap();
+            book.submit(sell("B", 5.0, 30)).unwrap();
+            book.submit(sell("C", 5.0, 60)).unwrap();
+            book.submit(sell("D", 4.0, 10)).unwrap();
+
+            // "Better prices should be filled first whatever the policy"
+            let transactions = book.submit(buy(43)).unwrap();
+            assert_eq!("D", transactions[0].selling_order().get_order_id());
+
+            let allocations = transactions[1..]
+                .iter()
+                .map(|transaction| {
+                    (
+                        transaction.selling_order().get_order_id(),
+                        transaction.traded_shares(),
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            assert_eq!(expected, allocations);
+            assert_eq!(
+                43,
+                transactions
+                    .iter()
+                    .map(|transaction| transaction.traded_shares())
+                    .sum::<u32>()
+            );
+            assert_eq!(vec![(5.0, 67)], book.depth(Side::Sell));
+            assert!(book.depth(Side::Buy).is_empty());
+        }
+    }
+
+    #[test]
+    fn halt_and_resume() {
+        let asset = Asset::new("HGLG11");
+        let mut book = OrderBook::new(asset.id().to_owned());
+
+        let seller =
+            Investor::new("123", "Foo", vec![(asset.id().to_owned(), 100)]);
+        let buyer = Investor::new("321", "Bar", vec![]);
+
+        let sell_order = |id| {
+            Order::<Sell, Open>::new(
+                asset.to_owned(),
+                seller.to_owned(),
+                id,
+                5.0,
+                10,
+            )
+            .resolve_type()
+        };
+        let buy_order = |id| {
+            Order::<Buy, Open>::new(
+                asset.to_owned(),
+                buyer.to_owned(),
+                id,
+                5.0,
+                10,
+            )
+            .resolve_type()
+        };
+
+        book.append(sell_order("A")).unwrap();
+        book.append(buy_order("B")).unwrap();
+        book.drain_events();
+
+        book.halt(
+            Halt::new()
+                .with_reason("News pending")
+                .with_cancel_all()
+                .with_queued_orders(),
+        )
+        .unwrap();
+
+        assert!(book.buy_orders.is_empty() && book.sell_orders.is_empty());
+        assert_eq!(2, book.drain_events().len());
         assert_eq!(
-            &OrderTransition::Closed(order_c.copy()),
-            partial_transaction.buying_order()
+            Some(&MarketEvent::TradingHalted {
+                asset_id: asset.id().into(),
+                reason: Some("News pending".into()),
+                cancelled_orders: 2,
+                queue_orders: true,
+            }),
+            book.drain_market_events().last()
         );
 
-        assert_eq!(1, book.transactions.len());
-        assert_eq!(1, book.sell_orders.len());
+        // "Orders received while halted should wait for the resume"
+        book.append(sell_order("C")).unwrap();
+        book.append(buy_order("D")).unwrap();
+        book.append(buy_order("E")).unwrap();
+        assert!(book.sell_orders.is_empty());
+
+        let transactions = book.resume().unwrap();
+
+        assert_eq!(1, transactions.len());
         assert_eq!(1, book.buy_orders.len());
-        assert_eq!(&order_b, book.buy_orders.peek().unwrap());
+        assert_eq!(SessionPhase::Continuous, book.phase());
+        assert_eq!(
+            Some(&MarketEvent::TradingResumed {
+                asset_id: asset.id().into(),
+                released_orders: 3,
+                rejected_orders: 0,
+            }),
+            book.drain_market_events().last()
+        );
 
-        /* 2º PARTIAL TRANSACTION */
+        // "Halts without a queue should keep rejecting orders"
+        book.halt(Halt::new()).unwrap();
+        assert_eq!(
+            OrderBookError::PhaseNotAcceptingOrders(SessionPhase::Halted),
+            book.append(buy_order("F")).unwrap_err()
+        );
+    }
 
-        let partial_transaction = book.try_match().unwrap();
-        const PARTIAL_TOTAL: f32 = ORDER_PRICE * PARTIAL_QUANTITY as f32;
+    #[test]
+    fn enforce_price_bands() {
+        use chrono::{Duration, TimeZone, Utc};
 
-        assert_eq!(PARTIAL_QUANTITY, partial_transaction.traded_shares());
-        assert_eq!(PARTIAL_TOTAL, partial_transaction.total());
+        use crate::session::{ManualClock, SessionCalendar};
 
-        assert!(order_a.sell(PARTIAL_QUANTITY).is_ok());
-        assert!(order_b.buy(PARTIAL_QUANTITY).is_ok());
+        let clock = Arc::new(ManualClock::new(
+            Utc.with_ymd_and_hms(2023, 8, 14, 13, 0, 0).unwrap(),
+        ));
 
-        assert_eq!(
-            &OrderTransition::Closed(order_a.copy()),
-            partial_transaction.selling_order()
+        let asset = Asset::new("HGLG11");
+        let mut book = OrderBook::new(asset.id().to_owned())
+            .with_session(TradingSession::new(
+                SessionCalendar::always_open(),
+                clock.clone(),
+            ))
+            .with_price_bands(
+                PriceBands::new()
+                    .with_static_band(50.0)
+                    .with_dynamic_band(5.0)
+                    .with_volatility_auction(Duration::minutes(2)),
+            );
+
+        book.set_reference_price(10.0);
+
+        let seller = Investor::new("123", "Foo", vec![("HGLG11".into(), 20)]);
+        let buyer = Investor::new("321", "Bar", vec![]);
+
+        let fat_finger = Order::<Buy, Open>::new(
+            asset.to_owned(),
+            buyer.to_owned(),
+            "A",
+            10000.0,
+            10,
         );
+
         assert_eq!(
-            &OrderTransition::Closed(order_b.copy()),
-            partial_transaction.buying_order()
+            OrderBookError::PriceOutOfBand {
+                low: 5.0,
+                high: 15.0
+            },
+            book.append(fat_finger.resolve_type()).unwrap_err()
         );
 
-        assert_eq!(2, book.transactions.len());
-        assert_eq!(0, book.sell_orders.len());
-        assert_eq!(0, book.buy_orders.len());
+        for (id, price) in [("B", 10.0), ("C", 12.0)] {
+            let order = Order::<Sell, Open>::new(
+                asset.to_owned(),
+                seller.to_owned(),
+                id,
+                price,
+                10,
+            );
+
+            book.append(order.resolve_type()).unwrap();
+        }
+
+        let order = Order::<Buy, Open>::new(
+            asset.to_owned(),
+            buyer.to_owned(),
+            "D",
+            10.0,
+            10,
+        );
+        book.append(order.resolve_type()).unwrap();
+        book.try_match().unwrap();
+
+        // "A trade 20% above the last one should start a volatility auction"
+        let order = Order::<Buy, Open>::new(asset, buyer, "E", 12.0, 10);
+        book.append(order.resolve_type()).unwrap();
 
         assert_eq!(
-            OrderBookError::NoMatchingOrderAvailable,
+            OrderBookError::VolatilityAuctionStarted,
             book.try_match().unwrap_err()
-        )
+        );
+        assert_eq!(SessionPhase::VolatilityAuction, book.phase());
+        assert_eq!(MatchingMode::Auction, book.mode());
+
+        clock.advance(Duration::minutes(2));
+        let transactions = book.update_session().unwrap();
+
+        assert_eq!(SessionPhase::Continuous, book.phase());
+        assert_eq!(1, transactions.len());
+        assert_eq!(12.0, transactions[0].price());
+    }
+
+    #[test]
+    fn enforce_reference_data() {
+        let asset = Asset::new("HGLG11");
+        let reference_data = ReferenceData::new()
+            .with_tick_size(0.01)
+            .with_round_lot(100)
+            .with_max_notional(100_000.0);
+
+        let mut book = OrderBook::new(asset.id().to_owned())
+            .with_reference_data(reference_data.to_owned());
+        let mut fractional_book =
+            OrderBook::new(asset.fractional().id().into())
+                .with_reference_data(reference_data.fractional());
+
+        let investor = Investor::new("123", "Foo", vec![]);
+        let order = |asset: &Asset, price, shares| {
+            Order::<Buy, Open>::new(
+                asset.to_owned(),
+                investor.to_owned(),
+                "A",
+                price,
+                shares,
+            )
+            .resolve_type()
+        };
+
+        assert!(book.append(order(&asset, 13.45, 200)).is_ok());
+        assert_eq!(
+            OrderBookError::InvalidTickSize(0.01),
+            book.append(order(&asset, 13.455, 200)).unwrap_err()
+        );
+        assert_eq!(
+            OrderBookError::InvalidLotSize(100),
+            book.append(order(&asset, 13.45, 150)).unwrap_err()
+        );
+        assert_eq!(
+            OrderBookError::NotionalAboveMaximum(100_000.0),
+            book.append(order(&asset, 13.45, 10_000)).unwrap_err()
+        );
+
+        // "Odd lots should be traded in the fractional book"
+        assert!(fractional_book
+            .append(order(&asset.fractional(), 13.45, 50))
+            .is_ok());
+        assert_eq!(
+            OrderBookError::QuantityAboveMaximum(99),
+            fractional_book
+                .append(order(&asset.fractional(), 13.45, 100))
+                .unwrap_err()
+        );
     }
 }
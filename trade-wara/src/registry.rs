@@ -0,0 +1,409 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    allocation::MatchingPolicy,
+    circuit_breaker::PriceBands,
+    dto::instrument_dto::InstrumentDTO,
+    entities::{
+        asset::{Asset, ReferenceData, FRACTIONAL_SUFFIX},
+        order::Side,
+    },
+    margin::MarginRequirement,
+    order_book::{OrderBook, SelfTradePrevention},
+    session::TradingSession,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum InstrumentStatus {
+    #[default]
+    Active,
+    Inactive,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RegistryError {
+    UnknownAsset(String),
+    InactiveAsset(String),
+    InvalidDefinitions(String),
+}
+
+/// Tradable asset with its metadata and trading rules.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(from = "InstrumentDTO")]
+pub struct Instrument {
+    asset: Asset,
+    name: String,
+    currency: String,
+    status: InstrumentStatus,
+    reference_data: ReferenceData,
+    price_bands: PriceBands,
+    self_trade_prevention: Option<SelfTradePrevention>,
+    odd_lots: bool,
+    dark_pool: bool,
+    matching_policy: MatchingPolicy,
+    margin_requirement: MarginRequirement,
+}
+
+impl Instrument {
+    pub fn new(asset_id: &str, name: &str, currency: &str) -> Instrument {
+        Instrument {
+            asset: Asset::new(asset_id),
+            name: name.into(),
+            currency: currency.into(),
+            status: InstrumentStatus::Active,
+            reference_data: ReferenceData::default(),
+            price_bands: PriceBands::default(),
+            self_trade_prevention: None,
+            odd_lots: false,
+            dark_pool: false,
+            matching_policy: MatchingPolicy::Fifo,
+            margin_requirement: MarginRequirement::default(),
+        }
+    }
+
+    pub fn with_status(mut self, status: InstrumentStatus) -> Instrument {
+        self.status = status;
+        self
+    }
+
+    pub fn with_reference_data(
+        mut self,
+        reference_data: ReferenceData,
+    ) -> Instrument {
+        self.reference_data = reference_data;
+        self
+    }
+
+    pub fn with_price_bands(mut self, price_bands: PriceBands) -> Instrument {
+        self.price_bands = price_bands;
+        self
+    }
+
+    pub fn with_self_trade_prevention(
+        mut self,
+        self_trade_prevention: SelfTradePrevention,
+    ) -> Instrument {
+        self.self_trade_prevention = Some(self_trade_prevention);
+        self
+    }
+
+    /// Also lists the asset on the fractional market.
+    pub fn with_odd_lots(mut self) -> Instrument {
+        self.odd_lots = true;
+        self
+    }
+
+    /// Also trades the asset in a dark book crossing at the lit midpoint.
+    pub fn with_dark_pool(mut self) -> Instrument {
+        self.dark_pool = true;
+        self
+    }
+
+    pub fn with_matching_policy(
+        mut self,
+        matching_policy: MatchingPolicy,
+    ) -> Instrument {
+        self.matching_policy = matching_policy;
+        self
+    }
+
+    /// Allows margin accounts to buy or sell short the asset with less
+    /// equity than its market value.
+    pub fn with_margin_requirement(
+        mut self,
+        margin_requirement: MarginRequirement,
+    ) -> Instrument {
+        self.margin_requirement = margin_requirement;
+        self
+    }
+
+    /// Instrument of the fractional market for odd lots of this one.
+    pub fn fractional(&self) -> Instrument {
+        Instrument {
+            asset: self.asset.fractional(),
+            reference_data: self.reference_data.fractional(),
+            odd_lots: false,
+            ..self.to_owned()
+        }
+    }
+
+    pub fn asset(&self) -> &Asset {
+        &self.asset
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn currency(&self) -> &str {
+        &self.currency
+    }
+
+    pub fn status(&self) -> InstrumentStatus {
+        self.status
+    }
+
+    pub fn reference_data(&self) -> &ReferenceData {
+        &self.reference_data
+    }
+
+    pub fn price_bands(&self) -> &PriceBands {
+        &self.price_bands
+    }
+
+    pub fn self_trade_prevention(&self) -> Option<SelfTradePrevention> {
+        self.self_trade_prevention
+    }
+
+    pub fn dark_pool(&self) -> bool {
+        self.dark_pool
+    }
+
+    pub fn matching_policy(&self) -> MatchingPolicy {
+        self.matching_policy
+    }
+
+    pub fn margin_requirement(&self) -> MarginRequirement {
+        self.margin_requirement
+    }
+
+    pub fn create_book(&self, session: TradingSession) -> OrderBook {
+        let mut book =
+            OrderBook::new(self.asset.id().into()).with_session(session);
+
+        self.update_book(&mut book);
+
+        book
+    }
+
+    /// Applies the rules of the instrument to `book`, returning the dark
+    /// orders cancelled when its dark pool is disabled.
+    pub fn update_book(&self, book: &mut OrderBook) -> Vec<(String, Side)> {
+        book.update_rules(
+            self.reference_data.to_owned(),
+            self.price_bands.to_owned(),
+            self.self_trade_prevention,
+            self.dark_pool,
+            self.matching_policy,
+        )
+    }
+}
+
+impl From<InstrumentDTO> for Instrument {
+    fn from(value: InstrumentDTO) -> Instrument {
+        let mut reference_data = ReferenceData::new();
+
+        if let Some(tick_size) = value.tick_size {
+            reference_data = reference_data.with_tick_size(tick_size);
+        }
+
+        for (from_price, tick_size) in value.tick_tiers {
+            reference_data =
+                reference_data.with_tick_tier(from_price, tick_size);
+        }
+
+        if let Some(round_lot) = value.round_lot {
+            reference_data = reference_data.with_round_lot(round_lot);
+        }
+
+        if let Some(min_quantity) = value.min_quantity {
+            reference_data = reference_data.with_min_quantity(min_quantity);
+        }
+
+        if let Some(max_quantity) = value.max_quantity {
+            reference_data = reference_data.with_max_quantity(max_quantity);
+        }
+
+        if let Some(max_notional) = value.max_notional {
+            reference_data = reference_data.with_max_notional(max_notional);
+        }
+
+        let mut price_bands = PriceBands::new();
+
+        if let Some(static_band) = value.static_band {
+            price_bands = price_bands.with_static_band(static_band);
+        }
+
+        if let Some(dynamic_band) = value.dynamic_band {
+            price_bands = price_bands.with_dynamic_band(dynamic_band);
+        }
+
+        let margin_requirement = match value.initial_margin {
+            Some(initial) => MarginRequirement::new(
+                initial,
+                value.maintenance_margin.unwrap_or(initial),
+            ),
+            None => MarginRequirement::default(),
+        };
+
+        Instrument {
+            asset: Asset::new(&value.id),
+            name: value.name,
+            currency: value.currency,
+            status: value.status,
+            reference_data,
+            price_bands,
+            self_trade_prevention: value.self_trade_prevention,
+            odd_lots: value.odd_lots,
+            dark_pool: value.dark_pool,
+            matching_policy: value.matching_policy,
+            margin_requirement,
+        }
+    }
+}
+
+/// Instruments allowed to trade, keyed by asset id. Fractional markets are
+/// resolved from the instrument of their round lot asset.
+#[derive(Debug, Default)]
+pub struct AssetRegistry {
+    instruments: HashMap<String, Instrument>,
+}
+
+impl AssetRegistry {
+    pub fn new() -> AssetRegistry {
+        AssetRegistry::default()
+    }
+
+    /// Reads a JSON array of instrument definitions.
+    pub fn load(path: &Path) -> Result<AssetRegistry, RegistryError> {
+        let json = fs::read_to_string(path).map_err(|err| {
+            RegistryError::InvalidDefinitions(err.to_string())
+        })?;
+
+        AssetRegistry::from_json(&json)
+    }
+
+    pub fn from_json(json: &str) -> Result<AssetRegistry, RegistryError> {
+        let instruments: Vec<Instrument> =
+            serde_json::from_str(json).map_err(|err| {
+                RegistryError::InvalidDefinitions(err.to_string())
+            })?;
+
+        Ok(instruments
+            .into_iter()
+            .fold(AssetRegistry::new(), AssetRegistry::with_instrument))
+    }
+
+    pub fn with_instrument(mut self, instrument: Instrument) -> AssetRegistry {
+        self.upsert(instrument);
+        self
+    }
+
+    /// Adds or replaces an instrument, returning the previous definition.
+    pub fn upsert(&mut self, instrument: Instrument) -> Option<Instrument> {
+        self.instruments
+            .insert(instrument.asset().id().into(), instrument)
+    }
+
+    pub fn get(&self, asset_id: &str) -> Option<&Instrument> {
+        self.instruments.get(asset_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.instruments.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instruments.is_empty()
+    }
+
+    /// Instrument an order for `asset_id` trades, failing for unknown and
+    /// inactive assets.
+    pub fn resolve(&self, asset_id: &str) -> Result<Instrument, RegistryError> {
+        let instrument = match self.get(asset_id) {
+            Some(instrument) => instrument.to_owned(),
+            None => asset_id
+                .strip_suffix(FRACTIONAL_SUFFIX)
+                .and_then(|asset_id| self.get(asset_id))
+                .filter(|instrument| instrument.odd_lots)
+                .map(Instrument::fractional)
+                .ok_or_else(|| RegistryError::UnknownAsset(asset_id.into()))?,
+        };
+
+        if instrument.status != InstrumentStatus::Active {
+            return Err(RegistryError::InactiveAsset(asset_id.into()));
+        }
+
+        Ok(instrument)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEFINITIONS: &str = r#"[
+        {
+            "id": "HGLG11",
+            "name": "CSHG Logística FII",
+            "currency": "BRL",
+            "round_lot": 100,
+            "static_band": 20.0,
+            "odd_lots": true
+        },
+        {
+            "id": "MXRF11",
+            "name": "Maxi Renda FII",
+            "currency": "BRL",
+            "status": "Inactive"
+        }
+    ]"#;
+
+    #[test]
+    fn resolve_instruments() {
+        let registry = AssetRegistry::from_json(DEFINITIONS).unwrap();
+
+        assert_eq!(2, registry.len());
+
+        let instrument = registry.resolve("HGLG11").unwrap();
+        assert_eq!("BRL", instrument.currency());
+        assert_eq!(100, instrument.reference_data().round_lot());
+        assert_eq!(
+            Some((8.0, 12.0)),
+            instrument.price_bands().static_limits(10.0)
+        );
+
+        let fractional = registry.resolve("HGLG11F").unwrap();
+        assert_eq!("HGLG11F", fractional.asset().id());
+        assert_eq!(1, fractional.reference_data().round_lot());
+
+        assert_eq!(
+            Err(RegistryError::InactiveAsset("MXRF11".into())),
+            registry.resolve("MXRF11")
+        );
+        assert_eq!(
+            Err(RegistryError::UnknownAsset("MXRF11F".into())),
+            registry.resolve("MXRF11F")
+        );
+        assert_eq!(
+            Err(RegistryError::UnknownAsset("HGLG1".into())),
+            registry.resolve("HGLG1")
+        );
+    }
+
+    #[test]
+    fn update_instruments() {
+        let mut registry = AssetRegistry::from_json(DEFINITIONS).unwrap();
+
+        let previous = registry.upsert(
+            Instrument::new("MXRF11", "Maxi Renda FII", "BRL")
+                .with_reference_data(ReferenceData::new().with_round_lot(10)),
+        );
+
+        assert_eq!(InstrumentStatus::Inactive, previous.unwrap().status());
+
+        let instrument = registry.resolve("MXRF11").unwrap();
+        let mut book = Instrument::new("MXRF11", "Maxi Renda FII", "BRL")
+            .create_book(TradingSession::default());
+
+        instrument.update_book(&mut book);
+        assert_eq!(10, book.reference_data().round_lot());
+
+        assert!(matches!(
+            AssetRegistry::from_json("{}"),
+            Err(RegistryError::InvalidDefinitions(_))
+        ));
+    }
+}
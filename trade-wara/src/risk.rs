@@ -0,0 +1,940 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Deserialize;
+
+use crate::{
+    entities::{
+        order::{OrderResolution, Side},
+        transaction::Transaction,
+    },
+    order_book::{OpenShares, OrderBook},
+};
+
+#[derive(Debug, PartialEq)]
+pub enum RiskError {
+    InvalidLimits(String),
+}
+
+/// Why the pre-trade risk stage rejected an order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RiskRejection {
+    RestrictedAsset(String),
+    OrderNotionalAboveLimit {
+        notional: f32,
+        limit: f32,
+    },
+    OpenOrdersAboveLimit(usize),
+    PositionAboveLimit {
+        position: i64,
+        limit: u32,
+    },
+    DailyLossLimitReached {
+        loss: f32,
+        limit: f32,
+    },
+    /// Short sale borrowing more shares than the located ones left.
+    LocateUnavailable {
+        shares: u32,
+        located: u32,
+    },
+}
+
+/// Limits applied to every order of the investors of a tier, unset limits
+/// are not checked.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct RiskLimits {
+    max_order_notional: Option<f32>,
+    max_open_orders: Option<usize>,
+    max_position: Option<u32>,
+    daily_loss_limit: Option<f32>,
+    restricted_assets: HashSet<String>,
+}
+
+impl RiskLimits {
+    pub fn new() -> RiskLimits {
+        RiskLimits::default()
+    }
+
+    pub fn with_max_order_notional(mut self, notional: f32) -> RiskLimits {
+        self.max_order_notional = Some(notional);
+        self
+    }
+
+    pub fn with_max_open_orders(mut self, open_orders: usize) -> RiskLimits {
+        self.max_open_orders = Some(open_orders);
+        self
+    }
+
+    /// Largest long or short position in shares of any single asset.
+    pub fn with_max_position(mut self, shares: u32) -> RiskLimits {
+        self.max_position = Some(shares);
+        self
+    }
+
+    /// Realized loss of the day after which new orders are rejected.
+    pub fn with_daily_loss_limit(mut self, loss: f32) -> RiskLimits {
+        self.daily_loss_limit = Some(loss);
+        self
+    }
+
+    pub fn with_restricted_asset(mut self, asset_id: &str) -> RiskLimits {
+        self.restricted_assets.insert(asset_id.into());
+        self
+    }
+
+    pub fn max_order_notional(&self) -> Option<f32> {
+        self.max_order_notional
+    }
+
+    pub fn max_open_orders(&self) -> Option<usize> {
+        self.max_open_orders
+    }
+
+    pub fn max_position(&self) -> Option<u32> {
+        self.max_position
+    }
+
+    pub fn daily_loss_limit(&self) -> Option<f32> {
+        self.daily_loss_limit
+    }
+
+    pub fn is_restricted(&self, asset_id: &str) -> bool {
+        self.restricted_assets.contains(asset_id)
+    }
+}
+
+/// Net shares of an investor in an asset, negative when short, and their
+/// average price.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Position {
+    shares: i64,
+    average_price: f32,
+}
+
+impl Position {
+    pub fn shares(&self) -> i64 {
+        self.shares
+    }
+
+    pub fn average_price(&self) -> f32 {
+        self.average_price
+    }
+
+    /// Applies a trade of `shares`, negative when selling, returning the
+    /// profit or loss realized by the shares it closes.
+    fn trade(&mut self, shares: i64, price: f32) -> f32 {
+        let closing = if self.shares.signum() == -shares.signum() {
+            shares.abs().min(self.shares.abs())
+        } else {
+            0
+        };
+        let realized = closing as f32
+            * (price - self.average_price)
+            * self.shares.signum() as f32;
+
+        let opening = shares.abs() - closing;
+        let new_shares = self.shares + shares;
+
+        if opening > 0 {
+            let kept = new_shares.abs() - opening;
+
+            self.average_price = (kept as f32 * self.average_price
+                + opening as f32 * price)
+                / new_shares.abs() as f32;
+        } else if new_shares == 0 {
+            self.average_price = 0.0;
+        }
+
+        self.shares = new_shares;
+
+        realized
+    }
+}
+
+/// Positions of an investor, the profit or loss realized on the day of its
+/// last trade and the shares located for it to borrow.
+#[derive(Debug, Default, Clone)]
+struct Exposure {
+    positions: HashMap<String, Position>,
+    realized_pnl: f32,
+    day: Option<NaiveDate>,
+    located: HashMap<String, u32>,
+}
+
+impl Exposure {
+    fn record(
+        &mut self,
+        asset_id: &str,
+        shares: i64,
+        price: f32,
+        day: NaiveDate,
+    ) {
+        if self.day != Some(day) {
+            self.day = Some(day);
+            self.realized_pnl = 0.0;
+        }
+
+        self.realized_pnl += self
+            .positions
+            .entry(asset_id.into())
+            .or_default()
+            .trade(shares, price);
+    }
+
+    fn realized_pnl(&self, day: NaiveDate) -> f32 {
+        match self.day {
+            Some(last_day) if last_day == day => self.realized_pnl,
+            _ => 0.0,
+        }
+    }
+}
+
+/// Order being checked, with the state of its investor.
+pub struct RiskContext<'a> {
+    pub order: &'a OrderResolution,
+    pub limits: &'a RiskLimits,
+    /// Unknown for market and pegged orders before the first trade.
+    pub notional: Option<f32>,
+    pub open_orders: usize,
+    /// Shares of the open orders of the investor in the asset.
+    pub open_shares: OpenShares,
+    pub position: Position,
+    pub realized_pnl: f32,
+    /// Located shares of the asset not used up by trades, including the
+    /// ones the open short sales of the investor would borrow.
+    pub located: u32,
+}
+
+/// Single check of the pre-trade risk stage.
+pub trait RiskCheck: Send {
+    fn check(&self, context: &RiskContext) -> Result<(), RiskRejection>;
+}
+
+pub struct RestrictedAssetCheck;
+
+impl RiskCheck for RestrictedAssetCheck {
+    fn check(&self, context: &RiskContext) -> Result<(), RiskRejection> {
+        let asset_id = context.order.asset_id();
+
+        if context.limits.is_restricted(asset_id) {
+            return Err(RiskRejection::RestrictedAsset(asset_id.into()));
+        }
+
+        Ok(())
+    }
+}
+
+pub struct OrderNotionalCheck;
+
+impl RiskCheck for OrderNotionalCheck {
+    fn check(&self, context: &RiskContext) -> Result<(), RiskRejection> {
+        if let (Some(limit), Some(notional)) =
+            (context.limits.max_order_notional(), context.notional)
+        {
+            if notional > limit {
+                return Err(RiskRejection::OrderNotionalAboveLimit {
+                    notional,
+                    limit,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub struct OpenOrdersCheck;
+
+impl RiskCheck for OpenOrdersCheck {
+    fn check(&self, context: &RiskContext) -> Result<(), RiskRejection> {
+        if let Some(limit) = context.limits.max_open_orders() {
+            if context.open_orders >= limit {
+                return Err(RiskRejection::OpenOrdersAboveLimit(limit));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Rejects orders that would take the position beyond the limit if filled
+/// with the open orders of the same side, unless they reduce it.
+pub struct PositionCheck;
+
+impl RiskCheck for PositionCheck {
+    fn check(&self, context: &RiskContext) -> Result<(), RiskRejection> {
+        let Some(limit) = context.limits.max_position() else {
+            return Ok(());
+        };
+
+        let shares = context.order.shares() as i64;
+        let current = context.position.shares();
+        let open_shares = context.open_shares;
+        let position = match context.order.side() {
+            Side::Buy => current + open_shares.buy as i64 + shares,
+            Side::Sell => current - open_shares.sell as i64 - shares,
+        };
+
+        if position.abs() > limit as i64 && position.abs() > current.abs() {
+            return Err(RiskRejection::PositionAboveLimit { position, limit });
+        }
+
+        Ok(())
+    }
+}
+
+pub struct DailyLossCheck;
+
+impl RiskCheck for DailyLossCheck {
+    fn check(&self, context: &RiskContext) -> Result<(), RiskRejection> {
+        if let Some(limit) = context.limits.daily_loss_limit() {
+            let loss = -context.realized_pnl;
+
+            if loss >= limit {
+                return Err(RiskRejection::DailyLossLimitReached {
+                    loss,
+                    limit,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Rejects short sales borrowing more shares than located and not reserved
+/// by the open short sales of the investor, which sells the shares it holds
+/// first.
+pub struct ShortSaleCheck;
+
+impl RiskCheck for ShortSaleCheck {
+    fn check(&self, context: &RiskContext) -> Result<(), RiskRejection> {
+        let order = context.order;
+
+        if order.side() != Side::Sell || !order.instructions().is_short_sell() {
+            return Ok(());
+        }
+
+        let held = order
+            .investor()
+            .assets()
+            .get(order.asset_id())
+            .copied()
+            .unwrap_or(0);
+        let shares = order.shares().saturating_sub(held);
+        let located = context.located.saturating_sub(context.open_shares.short);
+
+        if shares > located {
+            return Err(RiskRejection::LocateUnavailable { shares, located });
+        }
+
+        Ok(())
+    }
+}
+
+/// Limits read from a JSON file, by tier and investor.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct RiskDefinitions {
+    default: RiskLimits,
+    tiers: HashMap<String, RiskLimits>,
+    investors: HashMap<String, String>,
+}
+
+/// Checks every order before it reaches its order book, using the limits of
+/// the tier of its investor and the positions built by its trades.
+pub struct PreTradeRisk {
+    checks: Vec<Box<dyn RiskCheck>>,
+    default_limits: RiskLimits,
+    tiers: HashMap<String, RiskLimits>,
+    investor_tiers: HashMap<String, String>,
+    exposures: HashMap<String, Exposure>,
+    last_prices: HashMap<String, f32>,
+}
+
+impl Default for PreTradeRisk {
+    fn default() -> Self {
+        PreTradeRisk {
+            checks: vec![
+                Box::new(RestrictedAssetCheck),
+                Box::new(OrderNotionalCheck),
+                Box::new(OpenOrdersCheck),
+                Box::new(PositionCheck),
+                Box::new(DailyLossCheck),
+                Box::new(ShortSaleCheck),
+            ],
+            default_limits: RiskLimits::default(),
+            tiers: HashMap::new(),
+            investor_tiers: HashMap::new(),
+            exposures: HashMap::new(),
+            last_prices: HashMap::new(),
+        }
+    }
+}
+
+impl PreTradeRisk {
+    /// Runs the standard checks, without any limit set.
+    pub fn new() -> PreTradeRisk {
+        PreTradeRisk::default()
+    }
+
+    /// Reads the default limits, the limits of each tier and the tier of
+    /// each investor from a JSON file.
+    pub fn load(path: &Path) -> Result<PreTradeRisk, RiskError> {
+        let json = fs::read_to_string(path)
+            .map_err(|err| RiskError::InvalidLimits(err.to_string()))?;
+
+        PreTradeRisk::from_json(&json)
+    }
+
+    pub fn from_json(json: &str) -> Result<PreTradeRisk, RiskError> {
+        let definitions: RiskDefinitions = serde_json::from_str(json)
+            .map_err(|err| RiskError::InvalidLimits(err.to_string()))?;
+
+        let risk = definitions
+            .tiers
+            .into_iter()
+            .fold(PreTradeRisk::new(), |risk, (tier, limits)| {
+                risk.with_tier(&tier, limits)
+            })
+            .with_default_limits(definitions.default);
+
+        Ok(definitions.investors.into_iter().fold(
+            risk,
+            |risk, (investor_id, tier)| {
+                risk.with_investor_tier(&investor_id, &tier)
+            },
+        ))
+    }
+
+    /// Adds a check run after the ones already set.
+    pub fn with_check(mut self, check: impl RiskCheck + 'static) -> Self {
+        self.checks.push(Box::new(check));
+        self
+    }
+
+    /// Limits of the investors without a tier.
+    pub fn with_default_limits(mut self, limits: RiskLimits) -> Self {
+        self.default_limits = limits;
+        self
+    }
+
+    pub fn with_tier(mut self, tier: &str, limits: RiskLimits) -> Self {
+        self.tiers.insert(tier.into(), limits);
+        self
+    }
+
+    pub fn with_investor_tier(mut self, investor_id: &str, tier: &str) -> Self {
+        self.investor_tiers.insert(investor_id.into(), tier.into());
+        self
+    }
+
+    pub fn with_locate(
+        mut self,
+        investor_id: &str,
+        asset_id: &str,
+        shares: u32,
+    ) -> Self {
+        self.locate(investor_id, asset_id, shares);
+        self
+    }
+
+    /// Adds shares the investor may borrow to sell short, used up by its
+    /// short sales.
+    pub fn locate(&mut self, investor_id: &str, asset_id: &str, shares: u32) {
+        *self
+            .exposures
+            .entry(investor_id.into())
+            .or_default()
+            .located
+            .entry(asset_id.into())
+            .or_insert(0) += shares;
+    }
+
+    pub fn located(&self, investor_id: &str, asset_id: &str) -> u32 {
+        self.exposures
+            .get(investor_id)
+            .and_then(|exposure| exposure.located.get(asset_id))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Limits of the tier of an investor, or the default ones.
+    pub fn limits(&self, investor_id: &str) -> &RiskLimits {
+        self.investor_tiers
+            .get(investor_id)
+            .and_then(|tier| self.tiers.get(tier))
+            .unwrap_or(&self.default_limits)
+    }
+
+    pub fn position(&self, investor_id: &str, asset_id: &str) -> Position {
+        self.exposures
+            .get(investor_id)
+            .and_then(|exposure| exposure.positions.get(asset_id))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Profit or loss realized by an investor on the day of `now`.
+    pub fn realized_pnl(&self, investor_id: &str, now: DateTime<Utc>) -> f32 {
+        self.exposures
+            .get(investor_id)
+            .map_or(0.0, |exposure| exposure.realized_pnl(now.date_naive()))
+    }
+
+    /// Runs every check on an order, returning the first rejection.
+    pub fn check(
+        &self,
+        order: &OrderResolution,
+        books: &HashMap<String, OrderBook>,
+        now: DateTime<Utc>,
+    ) -> Result<(), RiskRejection> {
+        let investor_id = order.investor_id();
+        let asset_id = order.asset_id();
+        let instructions = order.instructions();
+
+        let price = if instructions.is_market() || instructions.peg().is_some()
+        {
+            self.last_prices.get(asset_id).copied()
+        } else {
+            Some(order.price())
+        };
+
+        let context = RiskContext {
+            order,
+            limits: self.limits(investor_id),
+            notional: price.map(|price| price * order.shares() as f32),
+            open_orders: books
+                .values()
+                .map(|book| book.open_orders(investor_id))
+                .sum(),
+            open_shares: books
+                .get(asset_id)
+                .map(|book| book.open_shares(investor_id))
+                .unwrap_or_default(),
+            position: self.position(investor_id, asset_id),
+            realized_pnl: self.realized_pnl(investor_id, now),
+            located: self.located(investor_id, asset_id),
+        };
+
+        self.checks
+            .iter()
+            .try_for_each(|check| check.check(&context))
+    }
+
+    /// Updates the positions of both investors of a trade, using up the
+    /// located shares of the seller it sold short.
+    pub fn record(&mut self, transaction: &Transaction) {
+        let asset_id = transaction.asset_id();
+        let shares = transaction.traded_shares() as i64;
+        let price = transaction.price();
+        let day = transaction.traded_at().date_naive();
+
+        self.last_prices.insert(asset_id.into(), price);
+
+        self.exposures
+            .entry(transaction.buying_order().investor_id().into())
+            .or_default()
+            .record(asset_id, shares, price, day);
+        let seller = self
+            .exposures
+            .entry(transaction.selling_order().investor_id().into())
+            .or_default();
+
+        seller.record(asset_id, -shares, price, day);
+
+        if let Some(located) = seller.located.get_mut(asset_id) {
+            *located = located.saturating_sub(transaction.short_shares());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::entities::{
+        asset::Asset,
+        investor::Investor,
+        order::{Buy, Open, Order, OrderInstructions, OrderItem, Sell},
+    };
+
+    use super::*;
+
+    #[test]
+    fn track_positions() {
+        let mut position = Position::default();
+
+        assert_eq!(0.0, position.trade(10, 5.0));
+        assert_eq!(0.0, position.trade(10, 7.0));
+        assert_eq!(6.0, position.average_price());
+
+        // "Selling beyond the position should close it and open a short"
+        assert_eq!(-40.0, position.trade(-25, 4.0));
+        assert_eq!(-5, position.shares());
+        assert_eq!(4.0, position.average_price());
+
+        assert_eq!(2.0, position.trade(2, 3.0));
+        assert_eq!(-3, position.shares());
+        assert_eq!(4.0, position.average_price());
+    }
+
+    #[test]
+    fn check_limits_by_tier() {
+        let asset = Asset::new("HGLG11");
+        let foo =
+            Investor::new("123", "Foo", vec![(asset.id().to_owned(), 1000)]);
+        let bar =
+            Investor::new("321", "Bar", vec![(asset.id().to_owned(), 100)]);
+
+        let mut risk = PreTradeRisk::new()
+            .with_default_limits(
+                RiskLimits::new().with_restricted_asset("HGLG11"),
+            )
+            .with_tier(
+                "retail",
+                RiskLimits::new()
+                    .with_max_order_notional(1_000.0)
+                    .with_max_open_orders(1)
+                    .with_max_position(150)
+                    .with_daily_loss_limit(50.0),
+            )
+            .with_investor_tier("321", "retail");
+
+        let mut books = HashMap::new();
+        let now = Utc::now();
+
+        let buy = |id: &str, price: f32, shares: u32| {
+            Order::<Buy, Open>::new(
+                asset.to_owned(),
+                bar.to_owned(),
+                id,
+                price,
+                shares,
+            )
+            .resolve_type()
+        };
+
+        assert_eq!(
+            Err(RiskRejection::RestrictedAsset("HGLG11".into())),
+            risk.check(
+                &Order::<Sell, Open>::new(
+                    asset.to_owned(),
+                    foo.to_owned(),
+                    "A",
+                    10.0,
+                    10,
+                )
+                .resolve_type(),
+                &books,
+                now,
+            )
+        );
+        assert_eq!(
+            Err(RiskRejection::OrderNotionalAboveLimit {
+                notional: 1_100.0,
+                limit: 1_000.0,
+            }),
+            risk.check(&buy("B", 11.0, 100), &books, now)
+        );
+        assert_eq!(Ok(()), risk.check(&buy("B", 10.0, 100), &books, now));
+
+        let mut book = OrderBook::new(asset.id().into());
+        book.submit(buy("B", 10.0, 100)).unwrap();
+        books.insert(asset.id().to_owned(), book);
+
+        assert_eq!(
+            Err(RiskRejection::OpenOrdersAboveLimit(1)),
+            risk.check(&buy("C", 10.0, 10), &books, now)
+        );
+
+        let transactions = books
+            .get_mut(asset.id())
+            .unwrap()
+            .submit(
+                Order::<Sell, Open>::new(
+                    asset.to_owned(),
+                    foo.to_owned(),
+                    "D",
+                    10.0,
+                    100,
+                )
+                .resolve_type(),
+            )
+            .unwrap();
+
+        transactions
+            .iter()
+            .for_each(|transaction| risk.record(transaction));
+
+        assert_eq!(100, risk.position("321", "HGLG11").shares());
+        assert_eq!(-100, risk.position("123", "HGLG11").shares());
+        assert_eq!(
+            Err(RiskRejection::PositionAboveLimit {
+                position: 160,
+                limit: 150,
+            }),
+            risk.check(&buy("E", 10.0, 60), &books, now)
+        );
+
+        // "Market orders should be valued at the last price"
+        let market_buy = Order::<Buy, Open>::new(
+            asset.to_owned(),
+            bar.to_owned(),
+            "F",
+            0.0,
+            50,
+        )
+        .with_instructions(OrderInstructions::new().with_market_price())
+        .resolve_type();
+
+        assert_eq!(Ok(()), risk.check(&market_buy, &books, now));
+
+        let book = books.get_mut(asset.id()).unwrap();
+
+        book.submit(
+            Order::<Sell, Open>::new(
+                asset.to_owned(),
+                bar.to_owned(),
+                "G",
+                9.0,
+                100,
+            )
+            .resolve_type(),
+        )
+        .unwrap();
+
+        let transactions = book
+            .submit(
+                Order::<Buy, Open>::new(
+                    asset.to_owned(),
+                    foo.to_owned(),
+                    "H",
+                    9.0,
+                    100,
+                )
+                .resolve_type(),
+            )
+            .unwrap();
+
+        transactions
+            .iter()
+            .for_each(|transaction| risk.record(transaction));
+
+        let now = transactions[0].traded_at();
+
+        assert_eq!(-100.0, risk.realized_pnl("321", now));
+        assert_eq!(
+            Err(RiskRejection::DailyLossLimitReached {
+                loss: 100.0,
+                limit: 50.0,
+            }),
+            risk.check(&buy("I", 9.0, 10), &books, now)
+        );
+    }
+
+    #[test]
+    fn check_positions_with_open_orders() {
+        let asset = Asset::new("HGLG11");
+        let bar =
+            Investor::new("321", "Bar", vec![(asset.id().to_owned(), 100)]);
+
+        let risk = PreTradeRisk::new()
+            .with_default_limits(RiskLimits::new().with_max_position(150));
+
+        let order = |id: &str, side: Side, shares: u32| match side {
+            Side::Buy => Order::<Buy, Open>::new(
+                asset.to_owned(),
+                bar.to_owned(),
+                id,
+                10.0,
+                shares,
+            )
+            .resolve_type(),
+            Side::Sell => Order::<Sell, Open>::new(
+                asset.to_owned(),
+                bar.to_owned(),
+                id,
+                12.0,
+                shares,
+            )
+            .resolve_type(),
+        };
+
+        let mut book = OrderBook::new(asset.id().into());
+        book.submit(order("A", Side::Buy, 100)).unwrap();
+        book.submit(order("B", Side::Sell, 100)).unwrap();
+
+        let books = HashMap::from([(asset.id().to_owned(), book)]);
+        let now = Utc::now();
+
+        // "Resting buys should count towards the position of a new buy"
+        assert_eq!(
+            Err(RiskRejection::PositionAboveLimit {
+                position: 160,
+                limit: 150,
+            }),
+            risk.check(&order("C", Side::Buy, 60), &books, now)
+        );
+        assert_eq!(Ok(()), risk.check(&order("C", Side::Buy, 50), &books, now));
+        assert_eq!(
+            Err(RiskRejection::PositionAboveLimit {
+                position: -160,
+                limit: 150,
+            }),
+            risk.check(&order("D", Side::Sell, 60), &books, now)
+        );
+    }
+
+    #[test]
+    fn check_short_sale_locates() {
+        let asset = Asset::new("HGLG11");
+        let foo =
+            Investor::new("123", "Foo", vec![(asset.id().to_owned(), 10)]);
+        let bar = Investor::new("321", "Bar", vec![]);
+
+        let mut risk = PreTradeRisk::new().with_locate("123", "HGLG11", 20);
+        let mut book = OrderBook::new(asset.id().into());
+
+        let short_sell = |shares: u32| {
+            Order::<Sell, Open>::new(
+                asset.to_owned(),
+                foo.to_owned(),
+                "A",
+                10.0,
+                shares,
+            )
+            .with_instructions(OrderInstructions::new().with_short_sell())
+            .resolve_type()
+        };
+
+        // "Held shares should be sold before borrowing located ones"
+        assert_eq!(
+            Err(RiskRejection::LocateUnavailable {
+                shares: 30,
+                located: 20,
+            }),
+            risk.check(&short_sell(40), &HashMap::new(), Utc::now())
+        );
+        assert_eq!(
+            Ok(()),
+            risk.check(&short_sell(25), &HashMap::new(), Utc::now())
+        );
+
+        book.submit(
+            Order::<Buy, Open>::new(asset.to_owned(), bar, "B", 10.0, 25)
+                .resolve_type(),
+        )
+        .unwrap();
+
+        let transactions = book.submit(short_sell(25)).unwrap();
+
+        assert_eq!(1, transactions.len());
+        assert!(transactions[0].is_short_sale());
+        assert_eq!(15, transactions[0].short_shares());
+
+        risk.record(&transactions[0]);
+
+        assert_eq!(5, risk.located("123", "HGLG11"));
+        assert_eq!(-25, risk.position("123", "HGLG11").shares());
+    }
+
+    #[test]
+    fn reserve_located_shares() {
+        let asset = Asset::new("HGLG11");
+        let foo = Investor::new("123", "Foo", vec![]);
+
+        let risk = PreTradeRisk::new().with_locate("123", "HGLG11", 30);
+
+        let short_sell = |id: &str| {
+            Order::<Sell, Open>::new(
+                asset.to_owned(),
+                foo.to_owned(),
+                id,
+                10.0,
+                25,
+            )
+            .with_instructions(OrderInstructions::new().with_short_sell())
+            .resolve_type()
+        };
+
+        let mut book = OrderBook::new(asset.id().into());
+        book.submit(short_sell("A")).unwrap();
+
+        let mut books = HashMap::from([(asset.id().to_owned(), book)]);
+
+        // "A resting short sale should hold on to its located shares"
+        assert_eq!(
+            Err(RiskRejection::LocateUnavailable {
+                shares: 25,
+                located: 5,
+            }),
+            risk.check(&short_sell("B"), &books, Utc::now())
+        );
+
+        books.get_mut("HGLG11").unwrap().cancel("A").unwrap();
+
+        assert_eq!(Ok(()), risk.check(&short_sell("B"), &books, Utc::now()));
+    }
+
+    #[test]
+    fn check_incoming_short_sales() {
+        let json = r#"{
+            "id": "A",
+            "investor_id": "123",
+            "investor_name": "Foo",
+            "asset_id": "HGLG11",
+            "price": 10.0,
+            "quantity": 25,
+            "short_sell": true,
+            "order_type": "Sell"
+        }"#;
+        let short_sell =
+            || -> OrderResolution { serde_json::from_str(json).unwrap() };
+
+        let mut risk = PreTradeRisk::new();
+
+        // "Every share of an incoming short sale should be borrowed"
+        assert_eq!(
+            Err(RiskRejection::LocateUnavailable {
+                shares: 25,
+                located: 0,
+            }),
+            risk.check(&short_sell(), &HashMap::new(), Utc::now())
+        );
+
+        risk.locate("123", "HGLG11", 30);
+
+        assert_eq!(
+            Ok(()),
+            risk.check(&short_sell(), &HashMap::new(), Utc::now())
+        );
+
+        let asset = Asset::new("HGLG11");
+        let mut book = OrderBook::new(asset.id().into());
+
+        book.submit(
+            Order::<Buy, Open>::new(
+                asset,
+                Investor::new("321", "Bar", vec![]),
+                "B",
+                10.0,
+                25,
+            )
+            .resolve_type(),
+        )
+        .unwrap();
+
+        let transactions = book.submit(short_sell()).unwrap();
+
+        assert_eq!(25, transactions[0].short_shares());
+
+        risk.record(&transactions[0]);
+
+        assert_eq!(5, risk.located("123", "HGLG11"));
+    }
+}
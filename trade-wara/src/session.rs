@@ -0,0 +1,357 @@
+use std::{
+    collections::HashSet,
+    fmt::Debug,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveTime, Utc,
+    Weekday,
+};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum SessionPhase {
+    PreOpen,
+    OpeningAuction,
+    Continuous,
+    ClosingAuction,
+    VolatilityAuction,
+    Closed,
+    Halted,
+}
+
+impl SessionPhase {
+    /// New orders are only accepted during calls and continuous trading,
+    /// while resting orders can be cancelled in every phase.
+    pub fn accepts_orders(&self) -> bool {
+        matches!(
+            self,
+            SessionPhase::OpeningAuction
+                | SessionPhase::Continuous
+                | SessionPhase::ClosingAuction
+                | SessionPhase::VolatilityAuction
+        )
+    }
+
+    pub fn is_auction(&self) -> bool {
+        matches!(
+            self,
+            SessionPhase::OpeningAuction
+                | SessionPhase::ClosingAuction
+                | SessionPhase::VolatilityAuction
+        )
+    }
+}
+
+pub trait Clock: Debug + Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+#[derive(Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Clock moved by hand, used to replay sessions and in tests.
+#[derive(Debug)]
+pub struct ManualClock {
+    now: Mutex<DateTime<Utc>>,
+}
+
+impl ManualClock {
+    pub fn new(now: DateTime<Utc>) -> ManualClock {
+        ManualClock {
+            now: Mutex::new(now),
+        }
+    }
+
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.lock().unwrap() = now;
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap()
+    }
+}
+
+/// Daily phases of a market in its local time. Outside the scheduled phases,
+/// on holidays and on non trading days the market is closed.
+#[derive(Debug, Clone)]
+pub struct SessionCalendar {
+    offset: FixedOffset,
+    phases: Vec<(NaiveTime, SessionPhase)>,
+    trading_days: Vec<Weekday>,
+    holidays: HashSet<NaiveDate>,
+}
+
+impl SessionCalendar {
+    pub fn new(offset: FixedOffset) -> SessionCalendar {
+        SessionCalendar {
+            offset,
+            phases: vec![],
+            trading_days: vec![
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+                Weekday::Fri,
+            ],
+            holidays: HashSet::new(),
+        }
+    }
+
+    /// Calendar trading continuously every day.
+    pub fn always_open() -> SessionCalendar {
+        SessionCalendar {
+            trading_days: vec![
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+                Weekday::Fri,
+                Weekday::Sat,
+                Weekday::Sun,
+            ],
+            ..SessionCalendar::new(FixedOffset::east_opt(0).unwrap())
+        }
+        .with_phase(NaiveTime::MIN, SessionPhase::Continuous)
+    }
+
+    /// Starts `phase` at the given local time, lasting until the next one.
+    pub fn with_phase(
+        mut self,
+        start: NaiveTime,
+        phase: SessionPhase,
+    ) -> SessionCalendar {
+        self.phases.push((start, phase));
+        self.phases.sort_by_key(|(start, _)| *start);
+        self
+    }
+
+    pub fn with_holiday(mut self, date: NaiveDate) -> SessionCalendar {
+        self.holidays.insert(date);
+        self
+    }
+
+    pub fn phase_at(&self, time: DateTime<Utc>) -> SessionPhase {
+        let local = time.with_timezone(&self.offset);
+
+        if !self.trading_days.contains(&local.weekday())
+            || self.holidays.contains(&local.date_naive())
+        {
+            return SessionPhase::Closed;
+        }
+
+        self.phases
+            .iter()
+            .take_while(|(start, _)| *start <= local.time())
+            .last()
+            .map(|(_, phase)| *phase)
+            .unwrap_or(SessionPhase::Closed)
+    }
+}
+
+/// Phase of a single asset, driven by its calendar unless halted or in a
+/// volatility auction.
+#[derive(Debug)]
+pub struct TradingSession {
+    calendar: SessionCalendar,
+    clock: Arc<dyn Clock>,
+    phase: SessionPhase,
+    halted: bool,
+    volatility_auction_until: Option<DateTime<Utc>>,
+}
+
+impl Default for TradingSession {
+    fn default() -> Self {
+        TradingSession::new(
+            SessionCalendar::always_open(),
+            Arc::new(SystemClock),
+        )
+    }
+}
+
+impl TradingSession {
+    pub fn new(
+        calendar: SessionCalendar,
+        clock: Arc<dyn Clock>,
+    ) -> TradingSession {
+        TradingSession {
+            phase: calendar.phase_at(clock.now()),
+            calendar,
+            clock,
+            halted: false,
+            volatility_auction_until: None,
+        }
+    }
+
+    pub fn phase(&self) -> SessionPhase {
+        self.phase
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+
+    /// Moves to the scheduled phase, returning the transition if any.
+    pub fn update(&mut self) -> Option<(SessionPhase, SessionPhase)> {
+        let now = self.clock.now();
+        let in_volatility_auction = self
+            .volatility_auction_until
+            .is_some_and(|until| until > now);
+
+        let phase = match (self.halted, in_volatility_auction) {
+            (true, _) => SessionPhase::Halted,
+            (false, true) => SessionPhase::VolatilityAuction,
+            (false, false) => self.calendar.phase_at(now),
+        };
+
+        if phase == self.phase {
+            return None;
+        }
+
+        let from = std::mem::replace(&mut self.phase, phase);
+
+        Some((from, phase))
+    }
+
+    pub fn halt(&mut self) -> Option<(SessionPhase, SessionPhase)> {
+        self.halted = true;
+        self.update()
+    }
+
+    pub fn resume(&mut self) -> Option<(SessionPhase, SessionPhase)> {
+        self.halted = false;
+        self.update()
+    }
+
+    /// Interrupts continuous trading for `duration`, after which the session
+    /// returns to its scheduled phase.
+    pub fn start_volatility_auction(
+        &mut self,
+        duration: Duration,
+    ) -> Option<(SessionPhase, SessionPhase)> {
+        self.volatility_auction_until = Some(self.clock.now() + duration);
+        self.update()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn b3_calendar() -> SessionCalendar {
+        SessionCalendar::new(FixedOffset::west_opt(3 * 3600).unwrap())
+            .with_phase(
+                NaiveTime::from_hms_opt(10, 0, 0).unwrap(),
+                SessionPhase::Continuous,
+            )
+            .with_phase(
+                NaiveTime::from_hms_opt(9, 30, 0).unwrap(),
+                SessionPhase::PreOpen,
+            )
+            .with_phase(
+                NaiveTime::from_hms_opt(9, 45, 0).unwrap(),
+                SessionPhase::OpeningAuction,
+            )
+            .with_phase(
+                NaiveTime::from_hms_opt(16, 55, 0).unwrap(),
+                SessionPhase::ClosingAuction,
+            )
+            .with_phase(
+                NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+                SessionPhase::Closed,
+            )
+            .with_holiday(NaiveDate::from_ymd_opt(2023, 9, 7).unwrap())
+    }
+
+    #[test]
+    fn scheduled_phases() {
+        let calendar = b3_calendar();
+        // 2023-08-14 is a Monday, B3 runs at UTC-3
+        let at = |hour, minute| {
+            Utc.with_ymd_and_hms(2023, 8, 14, hour, minute, 0).unwrap()
+        };
+
+        assert_eq!(SessionPhase::Closed, calendar.phase_at(at(12, 29)));
+        assert_eq!(SessionPhase::PreOpen, calendar.phase_at(at(12, 30)));
+        assert_eq!(SessionPhase::OpeningAuction, calendar.phase_at(at(12, 50)));
+        assert_eq!(SessionPhase::Continuous, calendar.phase_at(at(16, 0)));
+        assert_eq!(SessionPhase::ClosingAuction, calendar.phase_at(at(19, 56)));
+        assert_eq!(SessionPhase::Closed, calendar.phase_at(at(20, 0)));
+
+        let saturday = Utc.with_ymd_and_hms(2023, 8, 19, 16, 0, 0).unwrap();
+        let holiday = Utc.with_ymd_and_hms(2023, 9, 7, 16, 0, 0).unwrap();
+
+        assert_eq!(SessionPhase::Closed, calendar.phase_at(saturday));
+        assert_eq!(SessionPhase::Closed, calendar.phase_at(holiday));
+    }
+
+    #[test]
+    fn transition_phases() {
+        let clock = Arc::new(ManualClock::new(
+            Utc.with_ymd_and_hms(2023, 8, 14, 12, 0, 0).unwrap(),
+        ));
+
+        let mut session = TradingSession::new(b3_calendar(), clock.clone());
+
+        assert_eq!(SessionPhase::Closed, session.phase());
+        assert_eq!(None, session.update());
+
+        clock.advance(Duration::minutes(50));
+        assert_eq!(
+            Some((SessionPhase::Closed, SessionPhase::OpeningAuction)),
+            session.update()
+        );
+
+        assert_eq!(
+            Some((SessionPhase::OpeningAuction, SessionPhase::Halted)),
+            session.halt()
+        );
+
+        // "Halted sessions should ignore the calendar"
+        clock.advance(Duration::hours(1));
+        assert_eq!(None, session.update());
+
+        assert_eq!(
+            Some((SessionPhase::Halted, SessionPhase::Continuous)),
+            session.resume()
+        );
+
+        assert_eq!(
+            Some((SessionPhase::Continuous, SessionPhase::VolatilityAuction)),
+            session.start_volatility_auction(Duration::minutes(5))
+        );
+
+        clock.advance(Duration::minutes(4));
+        assert_eq!(None, session.update());
+
+        clock.advance(Duration::minutes(1));
+        assert_eq!(
+            Some((SessionPhase::VolatilityAuction, SessionPhase::Continuous)),
+            session.update()
+        );
+    }
+
+    #[test]
+    fn accept_orders_by_phase() {
+        assert!(SessionPhase::Continuous.accepts_orders());
+        assert!(SessionPhase::OpeningAuction.accepts_orders());
+        assert!(!SessionPhase::PreOpen.accepts_orders());
+        assert!(!SessionPhase::Halted.accepts_orders());
+        assert!(!SessionPhase::Closed.accepts_orders());
+    }
+}
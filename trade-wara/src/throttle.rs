@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// Orders accepted from an investor: `burst` at once, refilled at `rate`
+/// orders per second.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    rate: f64,
+    burst: u32,
+}
+
+impl RateLimit {
+    pub fn new(rate: f64, burst: u32) -> RateLimit {
+        RateLimit {
+            rate,
+            burst: burst.max(1),
+        }
+    }
+
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    pub fn burst(&self) -> u32 {
+        self.burst
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ThrottleError {
+    /// No order is accepted from the investor for `retry_after_ms`.
+    RateLimitExceeded {
+        investor_id: String,
+        retry_after_ms: i64,
+    },
+}
+
+/// Orders accepted and throttled for an investor.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct ThrottleMetrics {
+    pub investor_id: String,
+    pub accepted_orders: u64,
+    pub throttled_orders: u64,
+    /// RFC 3339 time of the last throttled order.
+    pub last_throttled_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TokenBucket {
+    tokens: f64,
+    updated_at: DateTime<Utc>,
+}
+
+impl TokenBucket {
+    fn full(limit: RateLimit, now: DateTime<Utc>) -> TokenBucket {
+        TokenBucket {
+            tokens: limit.burst as f64,
+            updated_at: now,
+        }
+    }
+
+    /// Takes a token if there is one, otherwise returns the milliseconds
+    /// until the next one.
+    fn take(
+        &mut self,
+        limit: RateLimit,
+        now: DateTime<Utc>,
+    ) -> Result<(), i64> {
+        let elapsed = (now - self.updated_at).num_microseconds().unwrap_or(0);
+
+        if elapsed > 0 {
+            self.tokens = (self.tokens + elapsed as f64 / 1e6 * limit.rate)
+                .min(limit.burst as f64);
+            self.updated_at = now;
+        }
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return Ok(());
+        }
+
+        if limit.rate <= 0.0 {
+            return Err(i64::MAX);
+        }
+
+        Err(((1.0 - self.tokens) / limit.rate * 1e3).ceil() as i64)
+    }
+}
+
+/// Limits the rate of orders of each investor with a token bucket, before
+/// they reach their book.
+#[derive(Debug)]
+pub struct Throttle {
+    default_limit: RateLimit,
+    limits: HashMap<String, RateLimit>,
+    buckets: HashMap<String, TokenBucket>,
+    metrics: HashMap<String, ThrottleMetrics>,
+}
+
+impl Throttle {
+    /// Applies `default_limit` to the investors without a limit of their own.
+    pub fn new(default_limit: RateLimit) -> Throttle {
+        Throttle {
+            default_limit,
+            limits: HashMap::new(),
+            buckets: HashMap::new(),
+            metrics: HashMap::new(),
+        }
+    }
+
+    pub fn with_limit(mut self, investor_id: &str, limit: RateLimit) -> Self {
+        self.limits.insert(investor_id.into(), limit);
+        self
+    }
+
+    pub fn limit(&self, investor_id: &str) -> RateLimit {
+        self.limits
+            .get(investor_id)
+            .copied()
+            .unwrap_or(self.default_limit)
+    }
+
+    /// Counts an order of the investor, rejecting it once its rate is
+    /// exceeded.
+    pub fn check(
+        &mut self,
+        investor_id: &str,
+        now: DateTime<Utc>,
+    ) -> Result<(), ThrottleError> {
+        let limit = self.limit(investor_id);
+        let taken = self
+            .buckets
+            .entry(investor_id.into())
+            .or_insert_with(|| TokenBucket::full(limit, now))
+            .take(limit, now);
+
+        let metrics =
+            self.metrics.entry(investor_id.into()).or_insert_with(|| {
+                ThrottleMetrics {
+                    investor_id: investor_id.into(),
+                    ..Default::default()
+                }
+            });
+
+        match taken {
+            Ok(()) => {
+                metrics.accepted_orders += 1;
+                Ok(())
+            }
+            Err(retry_after_ms) => {
+                metrics.throttled_orders += 1;
+                metrics.last_throttled_at = Some(now.to_rfc3339());
+
+                Err(ThrottleError::RateLimitExceeded {
+                    investor_id: investor_id.into(),
+                    retry_after_ms,
+                })
+            }
+        }
+    }
+
+    pub fn metrics(&self, investor_id: &str) -> Option<&ThrottleMetrics> {
+        self.metrics.get(investor_id)
+    }
+
+    /// Investors with throttled orders, the most throttled first.
+    pub fn throttled_investors(&self) -> Vec<&ThrottleMetrics> {
+        let mut throttled = self
+            .metrics
+            .values()
+            .filter(|metrics| metrics.throttled_orders > 0)
+            .collect::<Vec<_>>();
+
+        throttled.sort_by(|a, b| {
+            b.throttled_orders
+                .cmp(&a.throttled_orders)
+                .then_with(|| a.investor_id.cmp(&b.investor_id))
+        });
+
+        throttled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    #[test]
+    fn throttle_investor_bursts() {
+        let mut throttle = Throttle::new(RateLimit::new(2.0, 3))
+            .with_limit("321", RateLimit::new(100.0, 100));
+        let now = Utc::now();
+
+        for _ in 0..3 {
+            assert_eq!(Ok(()), throttle.check("123", now));
+        }
+
+        assert_eq!(
+            Err(ThrottleError::RateLimitExceeded {
+                investor_id: "123".into(),
+                retry_after_ms: 500,
+            }),
+            throttle.check("123", now)
+        );
+
+        // "Other investors should not be slowed down by a throttled one"
+        for _ in 0..50 {
+            assert_eq!(Ok(()), throttle.check("321", now));
+        }
+
+        // "The bucket should refill at the rate, up to the burst"
+        let later = now + Duration::milliseconds(500);
+
+        assert_eq!(Ok(()), throttle.check("123", later));
+        assert!(throttle.check("123", later).is_err());
+
+        let much_later = later + Duration::seconds(60);
+
+        for _ in 0..3 {
+            assert_eq!(Ok(()), throttle.check("123", much_later));
+        }
+
+        assert!(throttle.check("123", much_later).is_err());
+
+        let throttled = throttle.throttled_investors();
+
+        assert_eq!(1, throttled.len());
+        assert_eq!("123", throttled[0].investor_id);
+        assert_eq!(7, throttled[0].accepted_orders);
+        assert_eq!(3, throttled[0].throttled_orders);
+        assert_eq!(
+            Some(much_later.to_rfc3339()),
+            throttled[0].last_throttled_at
+        );
+    }
+}